@@ -3,15 +3,217 @@
 //! This module provides the `asset_def!` macro for defining assets in mods.
 //! The macro embeds the asset data into the WASM binary and communicates with the host
 //! to register the asset. The host returns an asset ID.
+//!
+//! Larger assets can be loaded lazily from the asset folder of the mod with `asset_load!`,
+//! which keeps them out of the WASM binary.
+//!
+//! An asset ID can be wrapped in a typed [`Handle`] to attach the asset to an entity,
+//! e.g. with the [`Sprite`](crate::builtin::Sprite) component.
+//!
+//! Text and data files, like a JSON config of the host, can be read with `asset_read!`,
+//! within the permissions the host granted to the mod.
+
+use crate::{HostModResult, __mod_free_memory, __mod_read_asset};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Handle to a host asset, by asset ID
+///
+/// The ID is either returned by `asset_def!` or `asset_load!` as `<mod name>:<path>`, or
+/// an asset path of the host.
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Handle<T> {
+    id: String,
+    #[serde(skip)]
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    /// Create a handle from an asset ID
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Get the asset ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Whether the handle points to no asset
+    pub fn is_empty(&self) -> bool {
+        self.id.is_empty()
+    }
+
+    /// Get the load state of the asset
+    pub fn load_state(&self) -> LoadState {
+        load_state(&self.id)
+    }
+}
+
+impl<T> Default for Handle<T> {
+    fn default() -> Self {
+        Self::new(String::new())
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.id.clone())
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Handle").field(&self.id).finish()
+    }
+}
+
+/// Load state of an asset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadState {
+    /// The asset is not loaded
+    NotLoaded,
+    /// The asset is being loaded
+    Loading,
+    /// The asset is loaded
+    Loaded,
+    /// The asset failed to load
+    Failed,
+}
+
+impl LoadState {
+    /// Whether the asset is loaded
+    pub fn is_loaded(&self) -> bool {
+        *self == LoadState::Loaded
+    }
+}
+
+/// Load an asset from the asset folder of the mod, returning its asset ID
+///
+/// The asset is loaded in the background, use [`load_state`] to know when it is ready.
+/// Returns an empty ID on failure.
+pub fn load_asset(asset_type: &str, path: &str) -> String {
+    // Buffer to hold the asset ID
+    let mut asset_id_buffer = [0u8; 256];
+
+    let asset_id_len = unsafe {
+        crate::__mod_load_asset(
+            asset_type.as_ptr(),
+            asset_type.len(),
+            path.as_ptr(),
+            path.len(),
+            asset_id_buffer.as_mut_ptr(),
+            asset_id_buffer.len(),
+        )
+    };
+
+    if asset_id_len > 0 && asset_id_len <= asset_id_buffer.len() {
+        String::from_utf8_lossy(&asset_id_buffer[..asset_id_len]).to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Get the load state of an asset by asset ID
+pub fn load_state(asset_id: &str) -> LoadState {
+    match unsafe { crate::__mod_asset_load_state(asset_id.as_ptr(), asset_id.len()) } {
+        1 => LoadState::Loading,
+        2 => LoadState::Loaded,
+        3 => LoadState::Failed,
+        _ => LoadState::NotLoaded,
+    }
+}
+
+/// Read the bytes of an asset, by asset ID or host asset path
+///
+/// Only text and bytes assets can be read. A host asset path starts loading on the first
+/// read, so this returns `None` until the asset is loaded, and when the mod is not allowed
+/// to read it.
+pub fn read_asset(asset_id: &str) -> Option<Vec<u8>> {
+    let mut result = HostModResult {
+        data_ptr: 0,
+        data_len: 0,
+    };
+    let result_ptr = &mut result as *mut HostModResult as *mut u8;
+
+    let data_len = unsafe { __mod_read_asset(asset_id.as_ptr(), asset_id.len(), result_ptr) };
+    if data_len == 0 || result.data_ptr == 0 {
+        return None;
+    }
+
+    let data = unsafe {
+        std::slice::from_raw_parts(result.data_ptr as *const u8, result.data_len as usize)
+    }
+    .to_vec();
+
+    // Free the memory allocated by the host
+    unsafe {
+        __mod_free_memory(result.data_ptr as *mut u8, result.data_len as usize);
+    }
+
+    Some(data)
+}
+
+/// Read an asset and deserialize it, by the extension of its path
+///
+/// `.json` files are read as JSON and `.ron` files as RON.
+pub fn read_asset_as<T: DeserializeOwned>(asset_id: &str) -> Option<T> {
+    let data = read_asset(asset_id)?;
+    let result = match asset_id.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("json") => serde_json::from_slice(&data).map_err(|e| e.to_string()),
+        Some("ron") => ron::de::from_bytes(&data).map_err(|e| e.to_string()),
+        _ => Err("unsupported format, expected a .json or .ron file".to_string()),
+    };
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            crate::log_error!("Failed to deserialize asset '{}': {}", asset_id, e);
+            None
+        }
+    }
+}
+
+/// Image asset, defined with `type=image`
+pub struct Image;
+
+/// Audio asset, defined with `type=audio`
+pub struct AudioSource;
+
+/// Text asset, defined with `type=text`
+pub struct TextAsset;
+
+/// Font asset, defined with `type=font`
+pub struct Font;
+
+/// Scene asset, defined with `type=scene`
+pub struct DynamicScene;
+
+/// First scene of a glTF file, defined with `type=gltf`
+pub struct Scene;
 
 /// Asset definition macro.
 ///
 /// This macro embeds the asset data into the WASM binary and communicates with the host
 /// to register the asset. The host returns an asset ID.
 ///
+/// The host handles the `image`, `audio`, `text`, `font`, `scene` and `gltf` types.
+///
 /// # Example
-/// ```rust
+/// ```rust,ignore
 /// let asset_id = asset_def!(type=image, src="path/to/image.png");
+/// spawn!(Sprite::from_image(Handle::new(asset_id)));
 /// ```
 #[macro_export]
 macro_rules! asset_def {
@@ -19,6 +221,7 @@ macro_rules! asset_def {
         // Read the asset file at compile time
         let data = include_bytes!($src);
         let asset_type = stringify!($type);
+        let asset_path: &str = $src;
 
         // Get the mod name
         let mod_name_ptr = unsafe { $crate::__mod_get_name() };
@@ -38,6 +241,8 @@ macro_rules! asset_def {
                 mod_name_bytes.len(),
                 asset_type.as_ptr(),
                 asset_type.len(),
+                asset_path.as_ptr(),
+                asset_path.len(),
                 data.as_ptr(),
                 data.len(),
                 asset_id_buffer.as_mut_ptr(),
@@ -64,3 +269,50 @@ macro_rules! asset_def {
         asset_id
     }};
 }
+
+/// Asset load macro.
+///
+/// This macro asks the host to load an asset from the asset folder of the mod through the
+/// `AssetServer`. The asset ID is returned immediately, while the asset loads in the
+/// background.
+///
+/// # Example
+/// ```rust,ignore
+/// let hero = Handle::<Image>::new(asset_load!(type=image, path="sprites/hero.png"));
+/// if hero.load_state().is_loaded() {
+///     spawn!(Sprite::from_image(hero));
+/// }
+/// ```
+#[macro_export]
+macro_rules! asset_load {
+    (type=$type:ident, path=$path:expr) => {
+        $crate::asset::load_asset(stringify!($type), $path)
+    };
+}
+
+/// Asset read macro.
+///
+/// This macro reads a text or bytes asset, by asset ID or host asset path. With a type,
+/// the asset is deserialized by the extension of its path, `.json` or `.ron`. Returns
+/// `None` while the asset is loading, or if the mod is not allowed to read it.
+///
+/// # Example
+/// ```rust,ignore
+/// #[derive(Deserialize)]
+/// struct Config {
+///     spawn_rate: f32,
+/// }
+///
+/// if let Some(config) = asset_read!(Config, "config/game.json") {
+///     log_info!("Spawn rate: {}", config.spawn_rate);
+/// }
+/// ```
+#[macro_export]
+macro_rules! asset_read {
+    ($id:expr) => {
+        $crate::asset::read_asset($id)
+    };
+    ($type:ty, $id:expr) => {
+        $crate::asset::read_asset_as::<$type>($id)
+    };
+}
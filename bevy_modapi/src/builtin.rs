@@ -0,0 +1,245 @@
+//! Built-in components and resources
+//!
+//! Mod-side versions of core Bevy types, and of Bevy components holding asset handles. The
+//! host registers them, so they can be spawned and queried like the components of the game
+//! SDK. `GlobalTransform`, `Children` and `Time` are computed by Bevy, they are not
+//! `Mutable` so mods can only read them.
+
+use crate::asset::{AudioSource, DynamicScene, Handle, Image, Scene};
+use crate::param::Entity;
+use bevy_modsdk::{Component, Mutable, Resource};
+use serde::{Deserialize, Serialize};
+
+/// Position, rotation and scale of an entity, mirroring Bevy's `Transform`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    /// Position of the entity
+    pub translation: [f32; 3],
+    /// Rotation of the entity, as a quaternion `[x, y, z, w]`
+    pub rotation: [f32; 4],
+    /// Scale of the entity
+    pub scale: [f32; 3],
+}
+
+impl Transform {
+    /// A transform without translation, rotation or scaling
+    pub const IDENTITY: Self = Self {
+        translation: [0.0; 3],
+        rotation: [0.0, 0.0, 0.0, 1.0],
+        scale: [1.0; 3],
+    };
+
+    /// Create a transform at a position
+    pub const fn from_xyz(x: f32, y: f32, z: f32) -> Self {
+        Self {
+            translation: [x, y, z],
+            ..Self::IDENTITY
+        }
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Component for Transform {
+    fn component_id() -> &'static str {
+        "bevy::Transform"
+    }
+}
+
+impl Mutable for Transform {}
+
+/// Transform of an entity relative to the world, mirroring Bevy's `GlobalTransform`
+///
+/// Computed by Bevy from the `Transform` of the entity and its parents.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GlobalTransform {
+    /// Position of the entity
+    pub translation: [f32; 3],
+    /// Rotation of the entity, as a quaternion `[x, y, z, w]`
+    pub rotation: [f32; 4],
+    /// Scale of the entity
+    pub scale: [f32; 3],
+}
+
+impl Component for GlobalTransform {
+    fn component_id() -> &'static str {
+        "bevy::GlobalTransform"
+    }
+}
+
+/// Name of an entity, mirroring Bevy's `Name`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Name(pub String);
+
+impl Name {
+    /// Create a name
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+}
+
+impl Component for Name {
+    fn component_id() -> &'static str {
+        "bevy::Name"
+    }
+}
+
+impl Mutable for Name {}
+
+/// Whether an entity is visible, mirroring Bevy's `Visibility`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Visibility {
+    /// Visible if the parent is visible
+    #[default]
+    Inherited,
+    /// Always hidden
+    Hidden,
+    /// Always visible
+    Visible,
+}
+
+impl Component for Visibility {
+    fn component_id() -> &'static str {
+        "bevy::Visibility"
+    }
+}
+
+impl Mutable for Visibility {}
+
+/// Parent of an entity, mirroring Bevy's `ChildOf`
+///
+/// Spawning an entity with it adds the entity to the `Children` of the parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChildOf(pub Entity);
+
+impl Component for ChildOf {
+    fn component_id() -> &'static str {
+        "bevy::ChildOf"
+    }
+}
+
+impl Mutable for ChildOf {}
+
+/// Children of an entity, mirroring Bevy's `Children`
+///
+/// Computed by Bevy from the `ChildOf` of the children.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Children(pub Vec<Entity>);
+
+impl Component for Children {
+    fn component_id() -> &'static str {
+        "bevy::Children"
+    }
+}
+
+/// Time of the frame, mirroring Bevy's `Time`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Time {
+    /// Seconds since the previous frame
+    pub delta_secs: f32,
+    /// Seconds since the startup
+    pub elapsed_secs: f32,
+    /// Seconds since the startup, with double precision
+    pub elapsed_secs_f64: f64,
+}
+
+impl Resource for Time {
+    fn resource_id() -> &'static str {
+        "bevy::Time"
+    }
+}
+
+/// A 2D sprite, mirroring Bevy's `Sprite`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sprite {
+    /// The image of the sprite
+    pub image: Handle<Image>,
+    /// The color tint, in sRGBA
+    pub color: [f32; 4],
+    /// Flip the sprite along the X axis
+    pub flip_x: bool,
+    /// Flip the sprite along the Y axis
+    pub flip_y: bool,
+    /// Size of the sprite, the image size if `None`
+    pub custom_size: Option<[f32; 2]>,
+}
+
+impl Sprite {
+    /// Create a sprite from an image
+    pub fn from_image(image: Handle<Image>) -> Self {
+        Self {
+            image,
+            ..Default::default()
+        }
+    }
+
+    /// Create a sprite of a solid color and size
+    pub fn from_color(color: [f32; 4], size: [f32; 2]) -> Self {
+        Self {
+            color,
+            custom_size: Some(size),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for Sprite {
+    fn default() -> Self {
+        Self {
+            image: Handle::default(),
+            color: [1.0; 4],
+            flip_x: false,
+            flip_y: false,
+            custom_size: None,
+        }
+    }
+}
+
+impl Component for Sprite {
+    fn component_id() -> &'static str {
+        "bevy::Sprite"
+    }
+}
+
+impl Mutable for Sprite {}
+
+/// Plays an audio asset, mirroring Bevy's `AudioPlayer`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioPlayer(pub Handle<AudioSource>);
+
+impl Component for AudioPlayer {
+    fn component_id() -> &'static str {
+        "bevy::AudioPlayer"
+    }
+}
+
+impl Mutable for AudioPlayer {}
+
+/// Spawns a scene, mirroring Bevy's `SceneRoot`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneRoot(pub Handle<Scene>);
+
+impl Component for SceneRoot {
+    fn component_id() -> &'static str {
+        "bevy::SceneRoot"
+    }
+}
+
+impl Mutable for SceneRoot {}
+
+/// Spawns a dynamic scene, mirroring Bevy's `DynamicSceneRoot`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DynamicSceneRoot(pub Handle<DynamicScene>);
+
+impl Component for DynamicSceneRoot {
+    fn component_id() -> &'static str {
+        "bevy::DynamicSceneRoot"
+    }
+}
+
+impl Mutable for DynamicSceneRoot {}
@@ -0,0 +1,47 @@
+//! Components defined by the mod.
+//!
+//! Mods can use components the host does not know about. The mod lists them with
+//! `component_def!`, and the host stores their data as the bytes the mods serialize, under
+//! their id. Other mods use them like the components of the game SDK, with a type marked
+//! `#[component(id = "...")]` of the same id.
+
+use bevy_modsdk::Component;
+use bevy_modtypes::ModComponentDef;
+
+/// Get the definition of a component, sent to the host when the mod is loaded.
+pub fn definition<T: Component>() -> ModComponentDef {
+    ModComponentDef {
+        id: T::component_id().to_string(),
+        schema_hash: T::schema_hash(),
+    }
+}
+
+/// Define the components of the mod unknown to the host.
+///
+/// The components are marked with `#[component(id = "...")]`, prefer ids starting with the
+/// name of the mod since they are shared by all mods.
+///
+/// ```ignore
+/// #[component(id = "my_mod::Health")]
+/// pub struct Health(pub u32);
+///
+/// component_def!(Health);
+/// ```
+#[macro_export]
+macro_rules! component_def {
+    ($($component:ty),* $(,)?) => {
+        // Get the components defined by the mod
+        #[unsafe(no_mangle)]
+        pub extern "C" fn __mod_components() -> *const $crate::HostModResult {
+            static COMPONENTS: std::sync::OnceLock<(Vec<u8>, $crate::HostModResult)> =
+                std::sync::OnceLock::new();
+            let (_, result) = COMPONENTS.get_or_init(|| {
+                let definitions = vec![$($crate::component::definition::<$component>()),*];
+                let mut storage = Vec::new();
+                let result = $crate::format::encode_result(&definitions, &mut storage);
+                (storage, result)
+            });
+            result as *const $crate::HostModResult
+        }
+    };
+}
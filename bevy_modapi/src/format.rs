@@ -0,0 +1,58 @@
+//! Serialization format of the data exchanged with the host.
+//!
+//! The host picks the format when loading the mod, among the formats both sides support.
+//! Mods use bincode until then, and support postcard and MessagePack with the `postcard`
+//! and `msgpack` features.
+
+use bevy_modtypes::{Format, FormatError, HostModResult};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Index of the format agreed with the host.
+static CURRENT: AtomicU32 = AtomicU32::new(0);
+
+/// Get the formats supported by the mod, as a bit mask of their indices.
+///
+/// Called by the host through the `__mod_formats` export of `system_def!`.
+pub fn supported_mask() -> u32 {
+    Format::supported_mask()
+}
+
+/// Set the format agreed with the host.
+///
+/// Called by the host through the `__mod_set_format` export of `system_def!`.
+pub fn set_current(index: u32) {
+    if Format::from_index(index).is_some_and(Format::is_supported) {
+        CURRENT.store(index, Ordering::Relaxed);
+    }
+}
+
+/// Get the format agreed with the host.
+pub fn current() -> Format {
+    Format::from_index(CURRENT.load(Ordering::Relaxed)).unwrap_or_default()
+}
+
+/// Serialize a value for the host.
+pub fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, FormatError> {
+    current().encode(value)
+}
+
+/// Deserialize a value sent by the host.
+pub fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, FormatError> {
+    current().decode(data)
+}
+
+/// Serialize a value into `storage`, for the host to read through the returned result.
+///
+/// The storage must stay alive until the host reads it, like the statics of the exports.
+pub fn encode_result<T: Serialize + ?Sized>(value: &T, storage: &mut Vec<u8>) -> HostModResult {
+    match encode(value) {
+        Ok(data) => *storage = data,
+        Err(e) => crate::log_error!("Failed to serialize data for the host: {}", e),
+    }
+    HostModResult {
+        data_ptr: storage.as_ptr() as u32,
+        data_len: storage.len() as u32,
+    }
+}
@@ -3,23 +3,42 @@
 //! This crate provides the API for Bevy mods.
 //! Mods can use these APIs to interact with the host game.
 
+// Allow the exported macros to be used inside this crate
+extern crate self as bevy_modapi;
+
 pub mod asset;
+pub mod builtin;
+pub mod component;
+pub mod format;
 pub mod log;
+pub mod param;
 pub mod query;
 pub mod resource;
+pub mod schema;
 pub mod spawn;
+pub mod storage;
+pub mod system;
 
 // Re-export the macros
 pub use bevy_modapi_macros::{system, system_def};
-pub use bevy_modsdk::Component;
-pub use bevy_modtypes::{HostModResult, SystemInfo};
+pub use bevy_modsdk::{Component, Mutable, Resource};
+pub use bevy_modtypes::{
+    Format, HostError, HostModResult, LogLevel, ModComponentDef, ModResourceDef, SystemAccess,
+    SystemInfo,
+};
+pub use asset::{Handle, LoadState};
+pub use builtin::{
+    AudioPlayer, ChildOf, Children, DynamicSceneRoot, GlobalTransform, Name, SceneRoot, Sprite,
+    Time, Transform, Visibility,
+};
+pub use param::{Entity, Mut, Query, Res, ResMut, SystemParam};
 
 // Host function declarations
 unsafe extern "C" {
-    /// Mod load function
+    /// Send a log record to the host
     ///
-    /// level: 0-debug 1-info 2-warn 3-error else-info
-    pub fn __mod_log(ptr: *const u8, len: usize, level: u8);
+    /// The record is a serialized `LogRecord`
+    pub fn __mod_log(record_ptr: *const u8, record_len: usize);
 
     /// Query components from the host
     /// Returns a pointer to serialized component data and the length
@@ -38,7 +57,31 @@ unsafe extern "C" {
     ) -> usize;
 
     /// Spawn entities with components
-    pub fn __mod_spawn_entities(components_ptr: *const u8, components_len: usize);
+    /// Returns 0 on success, or the code of a `HostError`
+    pub fn __mod_spawn_entities(components_ptr: *const u8, components_len: usize) -> u32;
+
+    /// Fetch the data of all parameters of a system in one call
+    /// Returns the length of the serialized data written to `result_ptr`
+    pub fn __mod_fetch_system_params(
+        requests_ptr: *const u8,
+        requests_len: usize,
+        result_ptr: *mut u8,
+    ) -> usize;
+
+    /// Apply the mutations made by a system
+    /// Returns 0 on success, or the code of a `HostError`
+    pub fn __mod_apply_system_writes(writes_ptr: *const u8, writes_len: usize) -> u32;
+
+    /// Enable or disable a system or a whole mod
+    ///
+    /// An empty mod name targets the calling mod, and an empty system name targets the whole mod.
+    pub fn __mod_set_system_enabled(
+        mod_name_ptr: *const u8,
+        mod_name_len: usize,
+        system_name_ptr: *const u8,
+        system_name_len: usize,
+        enabled: u8,
+    );
 
     /// Free memory allocated by the host
     pub fn __mod_free_memory(ptr: *mut u8, len: usize);
@@ -50,12 +93,60 @@ unsafe extern "C" {
         mod_name_len: usize,
         asset_type_ptr: *const u8,
         asset_type_len: usize,
+        asset_path_ptr: *const u8,
+        asset_path_len: usize,
         asset_data_ptr: *const u8,
         asset_data_len: usize,
         asset_id_ptr: *mut u8,
         asset_id_len: usize,
     ) -> usize;
 
+    /// Load an asset from the asset folder of the mod
+    /// Returns the length of the asset ID written to `asset_id_ptr`, 0 on failure
+    pub fn __mod_load_asset(
+        asset_type_ptr: *const u8,
+        asset_type_len: usize,
+        asset_path_ptr: *const u8,
+        asset_path_len: usize,
+        asset_id_ptr: *mut u8,
+        asset_id_len: usize,
+    ) -> usize;
+
+    /// Get the load state of an asset
+    ///
+    /// 0-not loaded 1-loading 2-loaded 3-failed
+    pub fn __mod_asset_load_state(asset_id_ptr: *const u8, asset_id_len: usize) -> u32;
+
+    /// Read the bytes of a text or bytes asset
+    /// Returns the length of the data, 0 while loading or if the asset cannot be read
+    pub fn __mod_read_asset(
+        asset_id_ptr: *const u8,
+        asset_id_len: usize,
+        result_ptr: *mut u8,
+    ) -> usize;
+
+    /// Check the schema hash of a component or resource against the host
+    ///
+    /// `kind` is 0 for components and 1 for resources. Returns 0 if the schema differs.
+    pub fn __mod_check_schema(kind: u32, id_ptr: *const u8, id_len: usize, hash: u64) -> u32;
+
+    /// Read a value of the mod storage
+    /// Returns the length of the value written to `result_ptr`, 0 if there is none
+    pub fn __mod_storage_get(key_ptr: *const u8, key_len: usize, result_ptr: *mut u8) -> usize;
+
+    /// Store a value in the mod storage
+    /// Returns 0 on success, or the code of a `HostError`
+    pub fn __mod_storage_set(
+        key_ptr: *const u8,
+        key_len: usize,
+        value_ptr: *const u8,
+        value_len: usize,
+    ) -> u32;
+
+    /// Remove a value from the mod storage
+    /// Returns 1 if the value existed
+    pub fn __mod_storage_remove(key_ptr: *const u8, key_len: usize) -> u32;
+
     /// Get mod name
     pub fn __mod_get_name() -> *const u8;
 }
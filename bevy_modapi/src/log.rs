@@ -1,59 +1,101 @@
+//! Logging for mods.
+//!
+//! Log records are sent to the host, which emits them through `bevy::log` along with
+//! the name of the mod and of the running system. Every macro accepts structured fields
+//! before the message, e.g. `log_info!(hp = 3, "hit")`.
+//!
+//! The host sets the minimum level of the mod, so records below it are dropped before
+//! being formatted, without calling the host.
+
+use bevy_modtypes::{LogLevel, LogRecord};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Index of the minimum level of the records sent to the host.
+static MIN_LEVEL: AtomicU32 = AtomicU32::new(0);
+
+/// Set the minimum level of the records sent to the host.
+///
+/// Called by the host through the `__mod_set_log_level` export of `system_def!`.
+pub fn set_min_level(index: u32) {
+    MIN_LEVEL.store(index, Ordering::Relaxed);
+}
+
+/// Whether records of a level are sent to the host.
+pub fn enabled(level: LogLevel) -> bool {
+    level.index() >= MIN_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Send a log record to the host.
+pub fn log_record(level: LogLevel, message: String, fields: Vec<(String, String)>) {
+    let record = LogRecord {
+        level,
+        message,
+        fields,
+    };
+    let Ok(data) = crate::format::encode(&record) else {
+        return;
+    };
+    unsafe {
+        crate::__mod_log(data.as_ptr(), data.len());
+    }
+}
+
+/// Log at a level, with optional `key = value` fields before the message.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log {
+    ($level:expr, $($key:ident = $value:expr),+ , $($arg:tt)+) => {
+        if $crate::log::enabled($level) {
+            $crate::log::log_record(
+                $level,
+                format!($($arg)+),
+                vec![$((stringify!($key).to_string(), format!("{}", $value))),+],
+            )
+        }
+    };
+    ($level:expr, $($arg:tt)+) => {
+        if $crate::log::enabled($level) {
+            $crate::log::log_record($level, format!($($arg)+), Vec::new())
+        }
+    };
+}
+
+/// Log macro trace
+#[macro_export]
+macro_rules! log_trace {
+    ($($arg:tt)+) => {
+        $crate::__log!($crate::LogLevel::Trace, $($arg)+)
+    };
+}
+
 /// Log macro debug
 #[macro_export]
 macro_rules! log_debug {
-    ($($arg:tt)*) => {
-        {
-            let msg = format!($($arg)*);
-            use std::ffi::CString;
-            let c_str = CString::new(msg).expect("CString::new failed");
-            unsafe {
-                bevy_modapi::__mod_log(c_str.as_ptr() as *const u8, c_str.as_bytes().len(), 0);
-            }
-        }
+    ($($arg:tt)+) => {
+        $crate::__log!($crate::LogLevel::Debug, $($arg)+)
     };
 }
 
 /// Log macro info
 #[macro_export]
 macro_rules! log_info {
-    ($($arg:tt)*) => {
-        {
-            let msg = format!($($arg)*);
-            use std::ffi::CString;
-            let c_str = CString::new(msg).expect("CString::new failed");
-            unsafe {
-                bevy_modapi::__mod_log(c_str.as_ptr() as *const u8, c_str.as_bytes().len(), 1);
-            }
-        }
+    ($($arg:tt)+) => {
+        $crate::__log!($crate::LogLevel::Info, $($arg)+)
     };
 }
 
 /// Log macro warn
 #[macro_export]
 macro_rules! log_warn {
-    ($($arg:tt)*) => {
-        {
-            let msg = format!($($arg)*);
-            use std::ffi::CString;
-            let c_str = CString::new(msg).expect("CString::new failed");
-            unsafe {
-                bevy_modapi::__mod_log(c_str.as_ptr() as *const u8, c_str.as_bytes().len(), 2);
-            }
-        }
+    ($($arg:tt)+) => {
+        $crate::__log!($crate::LogLevel::Warn, $($arg)+)
     };
 }
 
 /// Log macro error
 #[macro_export]
 macro_rules! log_error {
-    ($($arg:tt)*) => {
-        {
-            let msg = format!($($arg)*);
-            use std::ffi::CString;
-            let c_str = CString::new(msg).expect("CString::new failed");
-            unsafe {
-                bevy_modapi::__mod_log(c_str.as_ptr() as *const u8, c_str.as_bytes().len(), 3);
-            }
-        }
+    ($($arg:tt)+) => {
+        $crate::__log!($crate::LogLevel::Error, $($arg)+)
     };
 }
@@ -0,0 +1,585 @@
+//! System parameters for mods.
+//!
+//! A function marked with `#[system]` may take parameters such as
+//! `Query<(Entity, &mut Square)>`, `Res<Player>` or `ResMut<Player>`.
+//! Before the system runs, the data of all parameters is fetched from the host
+//! in a single call. Mutations made through `&mut` query items and `ResMut`
+//! are written back to the host after the system returns, for the changed values only.
+
+use crate::{HostModResult, __mod_apply_system_writes, __mod_fetch_system_params, __mod_free_memory};
+use bevy_modsdk::{Component, Mutable, Resource};
+use bevy_modtypes::{HostError, SystemAccess, SystemParamData, SystemParamRequest, SystemWrites};
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+
+thread_local! {
+    /// Mutations collected while the current system runs.
+    static PENDING_WRITES: RefCell<SystemWrites> = RefCell::new(SystemWrites::default());
+}
+
+/// Entity id in the host world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Entity(u64);
+
+impl Entity {
+    /// Create an entity from the bits returned by the host.
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    /// Get the bits of the entity, as understood by the host.
+    pub const fn to_bits(self) -> u64 {
+        self.0
+    }
+}
+
+/// A parameter of a mod system.
+///
+/// Implemented for [`Query`], [`Res`], [`ResMut`] and `Option<Res<T>>`.
+pub trait SystemParam: Sized {
+    /// Describe the data this parameter needs from the host.
+    fn request() -> SystemParamRequest;
+
+    /// Add the components and resources accessed by this parameter.
+    fn access(access: &mut SystemAccess);
+
+    /// Build the parameter from the data fetched by the host.
+    ///
+    /// Returns `None` if the parameter cannot be built, in which case the system is skipped.
+    fn from_data(data: Option<SystemParamData>) -> Option<Self>;
+}
+
+/// Data that can be fetched by a [`Query`].
+///
+/// Implemented for `&T`, `&mut T` where `T` is a component, for [`Entity`],
+/// and for tuples of these.
+pub trait QueryData {
+    /// Owned data fetched for one entity.
+    type Owned;
+    /// Item yielded by [`Query::iter_mut`].
+    type Item<'a>;
+    /// Item yielded by [`Query::iter`].
+    type ReadItem<'a>;
+
+    /// Push the ids of the fetched components.
+    fn component_ids(ids: &mut Vec<String>);
+
+    /// Add the components accessed by this data.
+    fn access(access: &mut SystemAccess);
+
+    /// Decode the data of one entity, consuming its components in order.
+    fn decode<'a>(
+        entity: Entity,
+        components: &mut impl Iterator<Item = &'a Vec<u8>>,
+    ) -> Option<Self::Owned>;
+
+    /// Borrow an item mutably.
+    fn item(owned: &mut Self::Owned) -> Self::Item<'_>;
+
+    /// Borrow an item immutably.
+    fn read_item(owned: &Self::Owned) -> Self::ReadItem<'_>;
+
+    /// Serialize the changed components of one entity.
+    fn write_back(owned: &Self::Owned, writes: &mut Vec<(String, Vec<u8>)>);
+}
+
+impl QueryData for Entity {
+    type Owned = Entity;
+    type Item<'a> = Entity;
+    type ReadItem<'a> = Entity;
+
+    fn component_ids(_ids: &mut Vec<String>) {}
+
+    fn access(_access: &mut SystemAccess) {}
+
+    fn decode<'a>(
+        entity: Entity,
+        _components: &mut impl Iterator<Item = &'a Vec<u8>>,
+    ) -> Option<Self::Owned> {
+        Some(entity)
+    }
+
+    fn item(owned: &mut Self::Owned) -> Self::Item<'_> {
+        *owned
+    }
+
+    fn read_item(owned: &Self::Owned) -> Self::ReadItem<'_> {
+        *owned
+    }
+
+    fn write_back(_owned: &Self::Owned, _writes: &mut Vec<(String, Vec<u8>)>) {}
+}
+
+impl<T: Component + DeserializeOwned + 'static> QueryData for &T {
+    type Owned = T;
+    type Item<'a> = &'a T;
+    type ReadItem<'a> = &'a T;
+
+    fn component_ids(ids: &mut Vec<String>) {
+        crate::schema::check_component::<T>();
+        ids.push(T::component_id().to_string());
+    }
+
+    fn access(access: &mut SystemAccess) {
+        access.component_reads.push(T::component_id().to_string());
+    }
+
+    fn decode<'a>(
+        _entity: Entity,
+        components: &mut impl Iterator<Item = &'a Vec<u8>>,
+    ) -> Option<Self::Owned> {
+        decode_component::<T>(components.next()?)
+    }
+
+    fn item(owned: &mut Self::Owned) -> Self::Item<'_> {
+        owned
+    }
+
+    fn read_item(owned: &Self::Owned) -> Self::ReadItem<'_> {
+        owned
+    }
+
+    fn write_back(_owned: &Self::Owned, _writes: &mut Vec<(String, Vec<u8>)>) {}
+}
+
+/// Mutable access to a component of a query item.
+///
+/// Only components borrowed mutably through it are written back to the host.
+pub struct Mut<'a, T> {
+    value: &'a mut T,
+    changed: &'a mut bool,
+}
+
+impl<T> Deref for Mut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for Mut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        *self.changed = true;
+        self.value
+    }
+}
+
+impl<T: Component + Mutable + Serialize + DeserializeOwned + 'static> QueryData for &mut T {
+    /// The component, and whether it was changed
+    type Owned = (T, bool);
+    type Item<'a> = Mut<'a, T>;
+    type ReadItem<'a> = &'a T;
+
+    fn component_ids(ids: &mut Vec<String>) {
+        crate::schema::check_component::<T>();
+        ids.push(T::component_id().to_string());
+    }
+
+    fn access(access: &mut SystemAccess) {
+        access.component_writes.push(T::component_id().to_string());
+    }
+
+    fn decode<'a>(
+        _entity: Entity,
+        components: &mut impl Iterator<Item = &'a Vec<u8>>,
+    ) -> Option<Self::Owned> {
+        decode_component::<T>(components.next()?).map(|component| (component, false))
+    }
+
+    fn item((value, changed): &mut Self::Owned) -> Self::Item<'_> {
+        Mut { value, changed }
+    }
+
+    fn read_item((value, _): &Self::Owned) -> Self::ReadItem<'_> {
+        value
+    }
+
+    fn write_back((value, changed): &Self::Owned, writes: &mut Vec<(String, Vec<u8>)>) {
+        if !changed {
+            return;
+        }
+        match crate::format::encode(value) {
+            Ok(data) => writes.push((T::component_id().to_string(), data)),
+            Err(e) => crate::log_error!("Failed to serialize component {}: {}", T::component_id(), e),
+        }
+    }
+}
+
+/// Implement [`QueryData`] for tuples
+macro_rules! impl_query_data_tuple {
+    ($($name:ident),+) => {
+        #[allow(non_snake_case)]
+        impl<$($name: QueryData),+> QueryData for ($($name,)+) {
+            type Owned = ($($name::Owned,)+);
+            type Item<'a> = ($($name::Item<'a>,)+);
+            type ReadItem<'a> = ($($name::ReadItem<'a>,)+);
+
+            fn component_ids(ids: &mut Vec<String>) {
+                $($name::component_ids(ids);)+
+            }
+
+            fn access(access: &mut SystemAccess) {
+                $($name::access(access);)+
+            }
+
+            fn decode<'a>(
+                entity: Entity,
+                components: &mut impl Iterator<Item = &'a Vec<u8>>,
+            ) -> Option<Self::Owned> {
+                Some(($($name::decode(entity, components)?,)+))
+            }
+
+            fn item(owned: &mut Self::Owned) -> Self::Item<'_> {
+                let ($($name,)+) = owned;
+                ($($name::item($name),)+)
+            }
+
+            fn read_item(owned: &Self::Owned) -> Self::ReadItem<'_> {
+                let ($($name,)+) = owned;
+                ($($name::read_item($name),)+)
+            }
+
+            fn write_back(owned: &Self::Owned, writes: &mut Vec<(String, Vec<u8>)>) {
+                let ($($name,)+) = owned;
+                $($name::write_back($name, writes);)+
+            }
+        }
+    };
+}
+
+impl_query_data_tuple!(A);
+impl_query_data_tuple!(A, B);
+impl_query_data_tuple!(A, B, C);
+impl_query_data_tuple!(A, B, C, D);
+impl_query_data_tuple!(A, B, C, D, E);
+impl_query_data_tuple!(A, B, C, D, E, F);
+impl_query_data_tuple!(A, B, C, D, E, F, G);
+impl_query_data_tuple!(A, B, C, D, E, F, G, H);
+
+/// Query of entities fetched from the host.
+///
+/// Components changed through [`Query::iter_mut`] and [`Query::get_mut`] are written
+/// back to the host after the system returns.
+pub struct Query<D: QueryData> {
+    entities: Vec<Entity>,
+    items: Vec<D::Owned>,
+}
+
+impl<D: QueryData> Query<D> {
+    /// Iterate over the query items
+    pub fn iter(&self) -> impl Iterator<Item = D::ReadItem<'_>> {
+        self.items.iter().map(D::read_item)
+    }
+
+    /// Iterate mutably over the query items
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = D::Item<'_>> {
+        self.items.iter_mut().map(D::item)
+    }
+
+    /// Get the item of an entity
+    pub fn get(&self, entity: Entity) -> Option<D::ReadItem<'_>> {
+        let index = self.entities.iter().position(|e| *e == entity)?;
+        Some(D::read_item(&self.items[index]))
+    }
+
+    /// Get the item of an entity mutably
+    pub fn get_mut(&mut self, entity: Entity) -> Option<D::Item<'_>> {
+        let index = self.entities.iter().position(|e| *e == entity)?;
+        Some(D::item(&mut self.items[index]))
+    }
+
+    /// Get the only item of the query, if there is exactly one
+    pub fn single(&self) -> Option<D::ReadItem<'_>> {
+        match self.items.as_slice() {
+            [item] => Some(D::read_item(item)),
+            _ => None,
+        }
+    }
+
+    /// Get the only item of the query mutably, if there is exactly one
+    pub fn single_mut(&mut self) -> Option<D::Item<'_>> {
+        match self.items.as_mut_slice() {
+            [item] => Some(D::item(item)),
+            _ => None,
+        }
+    }
+
+    /// Number of matched entities
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Whether no entity matched
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl<D: QueryData> SystemParam for Query<D> {
+    fn request() -> SystemParamRequest {
+        let mut components = Vec::new();
+        D::component_ids(&mut components);
+        SystemParamRequest::Query { components }
+    }
+
+    fn access(access: &mut SystemAccess) {
+        D::access(access);
+    }
+
+    fn from_data(data: Option<SystemParamData>) -> Option<Self> {
+        let mut entities = Vec::new();
+        let mut items = Vec::new();
+        if let Some(SystemParamData::Query(rows)) = data {
+            for (bits, components) in rows {
+                let entity = Entity::from_bits(bits);
+                if let Some(item) = D::decode(entity, &mut components.iter()) {
+                    entities.push(entity);
+                    items.push(item);
+                }
+            }
+        }
+        Some(Self { entities, items })
+    }
+}
+
+impl<D: QueryData> Drop for Query<D> {
+    fn drop(&mut self) {
+        PENDING_WRITES.with_borrow_mut(|pending| {
+            for (entity, item) in self.entities.iter().zip(&self.items) {
+                let mut writes = Vec::new();
+                D::write_back(item, &mut writes);
+                if !writes.is_empty() {
+                    pending.components.push((entity.to_bits(), writes));
+                }
+            }
+        });
+    }
+}
+
+/// Read-only access to a host resource.
+pub struct Res<T> {
+    value: T,
+}
+
+impl<T> Deref for Res<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Resource + DeserializeOwned> SystemParam for Res<T> {
+    fn request() -> SystemParamRequest {
+        crate::schema::check_resource::<T>();
+        SystemParamRequest::Resource {
+            id: T::resource_id().to_string(),
+        }
+    }
+
+    fn access(access: &mut SystemAccess) {
+        access.resource_reads.push(T::resource_id().to_string());
+    }
+
+    fn from_data(data: Option<SystemParamData>) -> Option<Self> {
+        match data {
+            Some(SystemParamData::Resource(Some(bytes))) => {
+                decode_resource::<T>(&bytes).map(|value| Self { value })
+            }
+            _ => {
+                crate::log_warn!("Resource {} not found", T::resource_id());
+                None
+            }
+        }
+    }
+}
+
+impl<T: Resource + DeserializeOwned> SystemParam for Option<Res<T>> {
+    fn request() -> SystemParamRequest {
+        Res::<T>::request()
+    }
+
+    fn access(access: &mut SystemAccess) {
+        Res::<T>::access(access);
+    }
+
+    fn from_data(data: Option<SystemParamData>) -> Option<Self> {
+        match data {
+            Some(SystemParamData::Resource(Some(bytes))) => {
+                Some(decode_resource::<T>(&bytes).map(|value| Res { value }))
+            }
+            _ => Some(None),
+        }
+    }
+}
+
+/// Mutable access to a host resource.
+///
+/// If the resource is changed, it is written back after the system returns.
+pub struct ResMut<T: Resource + Serialize> {
+    value: T,
+    changed: bool,
+}
+
+impl<T: Resource + Serialize> Deref for ResMut<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Resource + Serialize> DerefMut for ResMut<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.changed = true;
+        &mut self.value
+    }
+}
+
+impl<T: Resource + Mutable + Serialize + DeserializeOwned> SystemParam for ResMut<T> {
+    fn request() -> SystemParamRequest {
+        Res::<T>::request()
+    }
+
+    fn access(access: &mut SystemAccess) {
+        access.resource_writes.push(T::resource_id().to_string());
+    }
+
+    fn from_data(data: Option<SystemParamData>) -> Option<Self> {
+        Res::<T>::from_data(data).map(|res| Self {
+            value: res.value,
+            changed: false,
+        })
+    }
+}
+
+impl<T: Resource + Serialize> Drop for ResMut<T> {
+    fn drop(&mut self) {
+        if !self.changed {
+            return;
+        }
+        match crate::format::encode(&self.value) {
+            Ok(data) => PENDING_WRITES.with_borrow_mut(|pending| {
+                pending.resources.push((T::resource_id().to_string(), data));
+            }),
+            Err(e) => crate::log_error!("Failed to serialize resource {}: {}", T::resource_id(), e),
+        }
+    }
+}
+
+/// Fetch the data of all system parameters from the host in one call.
+///
+/// Used by the code generated by `#[system]`.
+pub fn fetch_params(requests: &[SystemParamRequest]) -> Vec<SystemParamData> {
+    let serialized_requests = match crate::format::encode(requests) {
+        Ok(data) => data,
+        Err(e) => {
+            crate::log_error!("Failed to serialize system param requests: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let mut result = HostModResult {
+        data_ptr: 0,
+        data_len: 0,
+    };
+    let result_ptr = &mut result as *mut HostModResult as *mut u8;
+
+    let data_len = unsafe {
+        __mod_fetch_system_params(
+            serialized_requests.as_ptr(),
+            serialized_requests.len(),
+            result_ptr,
+        )
+    };
+
+    if data_len == 0 || result.data_ptr == 0 {
+        return Vec::new();
+    }
+
+    let data_slice =
+        unsafe { std::slice::from_raw_parts(result.data_ptr as *const u8, result.data_len as usize) };
+    let data = match crate::format::decode::<Vec<SystemParamData>>(data_slice) {
+        Ok(data) => data,
+        Err(e) => {
+            crate::log_error!("Failed to deserialize system params: {}", e);
+            Vec::new()
+        }
+    };
+
+    // Free the memory allocated by the host
+    unsafe {
+        __mod_free_memory(result.data_ptr as *mut u8, result.data_len as usize);
+    }
+
+    data
+}
+
+/// Describe the access of a system to the host.
+///
+/// Used by the code generated by `#[system]`. The returned result points at the
+/// serialized access, which is kept alive by `storage`.
+pub fn encode_access(access: &SystemAccess, storage: &mut Vec<u8>) -> HostModResult {
+    match crate::format::encode(access) {
+        Ok(data) => *storage = data,
+        Err(e) => crate::log_error!("Failed to serialize system access: {}", e),
+    }
+    HostModResult {
+        data_ptr: storage.as_ptr() as u32,
+        data_len: storage.len() as u32,
+    }
+}
+
+/// Send the mutations collected while the system ran to the host.
+///
+/// Used by the code generated by `#[system]`.
+pub fn flush_writes() {
+    let writes = PENDING_WRITES.with_borrow_mut(std::mem::take);
+    if writes.components.is_empty() && writes.resources.is_empty() {
+        return;
+    }
+
+    match crate::format::encode(&writes) {
+        Ok(data) => {
+            let code = unsafe { __mod_apply_system_writes(data.as_ptr(), data.len()) };
+            if let Err(e) = HostError::from_code(code) {
+                crate::log_error!("Failed to apply system writes: {}", e);
+            }
+        }
+        Err(e) => crate::log_error!("Failed to serialize system writes: {}", e),
+    }
+}
+
+/// Drop the mutations collected by a system that did not run.
+///
+/// Used by the code generated by `#[system]`, parameters built before an unavailable one
+/// queue their writes when dropped.
+pub fn discard_writes() {
+    PENDING_WRITES.with_borrow_mut(std::mem::take);
+}
+
+/// Decode a component, logging on failure
+fn decode_component<T: Component + DeserializeOwned>(data: &[u8]) -> Option<T> {
+    match crate::format::decode::<T>(data) {
+        Ok(component) => Some(component),
+        Err(e) => {
+            crate::log_error!("Failed to deserialize component {}: {}", T::component_id(), e);
+            None
+        }
+    }
+}
+
+/// Decode a resource, logging on failure
+fn decode_resource<T: Resource + DeserializeOwned>(data: &[u8]) -> Option<T> {
+    match crate::format::decode::<T>(data) {
+        Ok(resource) => Some(resource),
+        Err(e) => {
+            crate::log_error!("Failed to deserialize resource {}: {}", T::resource_id(), e);
+            None
+        }
+    }
+}
@@ -3,11 +3,14 @@
 macro_rules! query {
     ($($component:ty),+) => {
         {
+            // Check the schemas before the first query
+            $(bevy_modapi::schema::check_component::<$component>();)+
+
             // Get component IDs
             let component_ids: Vec<&str> = vec![$(<$component>::component_id()),+];
 
             // Serialize component IDs
-            let serialized_ids = bincode::serde::encode_to_vec(&component_ids, bincode::config::standard())
+            let serialized_ids = bevy_modapi::format::encode(&component_ids)
                 .expect("Failed to serialize component IDs");
 
             // Call host function to query components
@@ -37,8 +40,8 @@ macro_rules! query {
                 };
 
                 // First, deserialize into Vec<Vec<Vec<u8>>> - entities with their component data
-                match bincode::serde::decode_from_slice::<Vec<Vec<Vec<u8>>>, _>(data_slice, bincode::config::standard()) {
-                    Ok((serialized_entities, _)) => {
+                match bevy_modapi::format::decode::<Vec<Vec<Vec<u8>>>>(data_slice) {
+                    Ok(serialized_entities) => {
                         // For each entity, deserialize its components
                         for serialized_components in serialized_entities {
                             // Check we have the right number of components
@@ -54,8 +57,8 @@ macro_rules! query {
                             $(
                                 let component_data = &serialized_components[index];
                                 index += 1;
-                                match bincode::serde::decode_from_slice::<$component, _>(component_data, bincode::config::standard()) {
-                                    Ok((component, _)) => {
+                                match bevy_modapi::format::decode::<$component>(component_data) {
+                                    Ok(component) => {
                                         deserialized.push(Ok(Box::new(component)));
                                     }
                                     Err(e) => {
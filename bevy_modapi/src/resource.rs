@@ -1,13 +1,72 @@
+//! Resources of the host, and resources defined by the mod.
+//!
+//! Mods can define resources the host does not know about with `resource_def!`. The host
+//! stores them in the world, so they keep their value when the mod is reloaded, and other
+//! mods use them by id like the resources of the game SDK.
+
+use bevy_modsdk::Resource;
+use bevy_modtypes::ModResourceDef;
+use serde::Serialize;
+
+/// Get the definition of a resource, sent to the host when the mod is loaded.
+///
+/// The default value of the resource is its initial value.
+pub fn definition<T: Resource + Default + Serialize>() -> ModResourceDef {
+    let initial = crate::format::encode(&T::default()).unwrap_or_else(|e| {
+        crate::log_error!("Failed to serialize resource {}: {}", T::resource_id(), e);
+        Vec::new()
+    });
+    ModResourceDef {
+        id: T::resource_id().to_string(),
+        schema_hash: T::schema_hash(),
+        initial,
+    }
+}
+
+/// Define the resources of the mod unknown to the host.
+///
+/// The resources are marked with `#[resource(id = "...")]` and implement `Default`, prefer
+/// ids starting with the name of the mod since they are shared by all mods.
+///
+/// ```ignore
+/// #[resource(id = "my_mod::Score")]
+/// #[derive(Default)]
+/// pub struct Score(pub u32);
+///
+/// resource_def!(Score);
+/// ```
+#[macro_export]
+macro_rules! resource_def {
+    ($($resource:ty),* $(,)?) => {
+        // Get the resources defined by the mod
+        #[unsafe(no_mangle)]
+        pub extern "C" fn __mod_resources() -> *const $crate::HostModResult {
+            static RESOURCES: std::sync::OnceLock<(Vec<u8>, $crate::HostModResult)> =
+                std::sync::OnceLock::new();
+            let (_, result) = RESOURCES.get_or_init(|| {
+                let definitions = vec![$($crate::resource::definition::<$resource>()),*];
+                let mut storage = Vec::new();
+                let result = $crate::format::encode_result(&definitions, &mut storage);
+                (storage, result)
+            });
+            result as *const $crate::HostModResult
+        }
+    };
+}
+
 /// Query macro for querying a resource from the host
 #[macro_export]
 macro_rules! res {
     ($resource:ty) => {
         {
+            // Check the schema before the first query
+            bevy_modapi::schema::check_resource::<$resource>();
+
             // Get resource ID
             let resource_id: &str = <$resource>::resource_id();
 
             // Serialize resource ID
-            let serialized_id = bincode::serde::encode_to_vec(&resource_id, bincode::config::standard())
+            let serialized_id = bevy_modapi::format::encode(&resource_id)
                 .expect("Failed to serialize resource ID");
 
             // Call host function to query resource
@@ -34,8 +93,8 @@ macro_rules! res {
                 };
 
                 // Deserialize the resource
-                match bincode::serde::decode_from_slice::<$resource, _>(data_slice, bincode::config::standard()) {
-                    Ok((resource, _)) => {
+                match bevy_modapi::format::decode::<$resource>(data_slice) {
+                    Ok(resource) => {
                         // Free the memory allocated by the host
                         unsafe {
                             __mod_free_memory(result.data_ptr as *mut u8, result.data_len as usize);
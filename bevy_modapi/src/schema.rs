@@ -0,0 +1,46 @@
+//! Schema checks
+//!
+//! The first time a mod uses a component or resource, the schema hash of its type is sent
+//! to the host. If it differs from the host type, the host reports an error for the mod and
+//! does not exchange the data of that type, so queries of it stay empty.
+
+use crate::__mod_check_schema;
+use bevy_modsdk::{Component, Resource};
+use bevy_modtypes::SchemaKind;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+thread_local! {
+    /// Result of the checks done so far
+    static CHECKED: RefCell<HashMap<(SchemaKind, &'static str), bool>> = RefCell::new(HashMap::new());
+}
+
+/// Check the schema of a component against the host, once per id
+///
+/// Returns whether the component matches the host.
+pub fn check_component<T: Component>() -> bool {
+    check(SchemaKind::Component, T::component_id(), T::schema_hash())
+}
+
+/// Check the schema of a resource against the host, once per id
+///
+/// Returns whether the resource matches the host.
+pub fn check_resource<T: Resource>() -> bool {
+    check(SchemaKind::Resource, T::resource_id(), T::schema_hash())
+}
+
+/// Check a schema hash against the host, once per id
+fn check(kind: SchemaKind, id: &'static str, hash: u64) -> bool {
+    // Types without a hash are not checked
+    if hash == 0 {
+        return true;
+    }
+    if let Some(matches) = CHECKED.with_borrow(|checked| checked.get(&(kind, id)).copied()) {
+        return matches;
+    }
+
+    // The host reports the mismatch
+    let matches = unsafe { __mod_check_schema(kind.index(), id.as_ptr(), id.len(), hash) } != 0;
+    CHECKED.with_borrow_mut(|checked| checked.insert((kind, id), matches));
+    matches
+}
@@ -3,13 +3,35 @@
 /// This macro allows mods to spawn entities with specified components.
 /// The syntax is `spawn!(component1, component2, ...);`
 /// where each component is a tuple of (ComponentType, ComponentValue).
+/// Failures are logged, use [`try_spawn!`](crate::try_spawn) to handle them.
 ///
 /// Example:
-/// ```rust
+/// ```rust,ignore
 /// spawn!(Square(Vec2 { x: 100.0, y: 120.0 }), Rect(IVec2 { x: 60, y: 64 }));
 /// ```
 #[macro_export]
 macro_rules! spawn {
+    ($($component:expr),+ $(,)?) => {
+        {
+            if let Err(e) = bevy_modapi::try_spawn!($($component),+) {
+                bevy_modapi::log_error!("Failed to spawn entity: {}", e);
+            }
+        }
+    };
+}
+
+/// Spawn entities with components, returning the error of the host
+///
+/// Nothing is spawned if a component is unknown to the host or cannot be decoded.
+///
+/// Example:
+/// ```rust,ignore
+/// if let Err(e) = try_spawn!(Square(Vec2 { x: 100.0, y: 120.0 })) {
+///     log_warn!("No square: {}", e);
+/// }
+/// ```
+#[macro_export]
+macro_rules! try_spawn {
     ($($component:expr),+ $(,)?) => {
         {
             // Create a vector of components
@@ -19,27 +41,29 @@ macro_rules! spawn {
                     // Use the component_id() method to get the component ID
                     let component_id = {
                         fn get_component_id<T: bevy_modapi::Component>(_: &T) -> &'static str {
+                            bevy_modapi::schema::check_component::<T>();
                             T::component_id()
                         }
                         get_component_id(&component)
                     };
-                    let serialized_component = bincode::serde::encode_to_vec(&component, bincode::config::standard())
+                    let serialized_component = bevy_modapi::format::encode(&component)
                         .expect("Failed to serialize component");
                     (component_id, serialized_component)
                 }),+
             ];
 
             // Serialize the components vector
-            let serialized_components = bincode::serde::encode_to_vec(&components, bincode::config::standard())
+            let serialized_components = bevy_modapi::format::encode(&components)
                 .expect("Failed to serialize components");
 
             // Call the host function to spawn entities
-            unsafe {
+            let code = unsafe {
                 bevy_modapi::__mod_spawn_entities(
                     serialized_components.as_ptr(),
                     serialized_components.len(),
-                );
-            }
+                )
+            };
+            bevy_modapi::HostError::from_code(code)
         }
     };
 }
@@ -0,0 +1,63 @@
+//! Key-value storage of the mod.
+//!
+//! The host keeps the values of each mod, so they survive reloading the mod, unlike the
+//! statics of the mod, and tools of the host can inspect them. A mod only reaches its own
+//! values.
+
+use crate::{
+    HostError, HostModResult, __mod_free_memory, __mod_storage_get, __mod_storage_remove,
+    __mod_storage_set,
+};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Get a stored value
+///
+/// Returns `None` if there is no value for the key or it does not decode as `T`.
+pub fn get<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let mut result = HostModResult {
+        data_ptr: 0,
+        data_len: 0,
+    };
+    let result_ptr = &mut result as *mut HostModResult as *mut u8;
+
+    let data_len = unsafe { __mod_storage_get(key.as_ptr(), key.len(), result_ptr) };
+    if data_len == 0 || result.data_ptr == 0 {
+        return None;
+    }
+
+    let data = unsafe {
+        std::slice::from_raw_parts(result.data_ptr as *const u8, result.data_len as usize)
+    };
+    let value = crate::format::decode(data);
+
+    // Free the memory allocated by the host
+    unsafe {
+        __mod_free_memory(result.data_ptr as *mut u8, result.data_len as usize);
+    }
+
+    match value {
+        Ok(value) => Some(value),
+        Err(e) => {
+            crate::log_error!("Failed to deserialize stored value {}: {}", key, e);
+            None
+        }
+    }
+}
+
+/// Store a value, replacing the previous one
+pub fn set<T: Serialize + ?Sized>(key: &str, value: &T) -> Result<(), HostError> {
+    let data = crate::format::encode(value).map_err(|e| {
+        crate::log_error!("Failed to serialize stored value {}: {}", key, e);
+        HostError::InvalidData
+    })?;
+    let code = unsafe { __mod_storage_set(key.as_ptr(), key.len(), data.as_ptr(), data.len()) };
+    HostError::from_code(code)
+}
+
+/// Remove a stored value
+///
+/// Returns whether there was a value for the key.
+pub fn remove(key: &str) -> bool {
+    unsafe { __mod_storage_remove(key.as_ptr(), key.len()) != 0 }
+}
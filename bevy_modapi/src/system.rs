@@ -0,0 +1,27 @@
+//! System state API for mods.
+//!
+//! Mods can enable or disable their own systems, or the systems of other mods.
+//! Changes take effect for the systems that run after the calling system.
+
+/// Enable or disable a system of the calling mod
+pub fn set_system_enabled(system_name: &str, enabled: bool) {
+    set_mod_system_enabled("", system_name, enabled);
+}
+
+/// Enable or disable a system of a mod
+pub fn set_mod_system_enabled(mod_name: &str, system_name: &str, enabled: bool) {
+    unsafe {
+        crate::__mod_set_system_enabled(
+            mod_name.as_ptr(),
+            mod_name.len(),
+            system_name.as_ptr(),
+            system_name.len(),
+            enabled as u8,
+        );
+    }
+}
+
+/// Enable or disable all systems of a mod
+pub fn set_mod_enabled(mod_name: &str, enabled: bool) {
+    set_mod_system_enabled(mod_name, "", enabled);
+}
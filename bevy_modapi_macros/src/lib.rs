@@ -6,13 +6,17 @@ extern crate proc_macro;
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::{
-    Ident, ItemFn, Token, parse::Parse, parse::ParseStream, parse_macro_input,
+    FnArg, Ident, ItemFn, Token, parse::Parse, parse::ParseStream, parse_macro_input,
     punctuated::Punctuated,
 };
 
 /// System macro.
 ///
-/// Add this macro to your Fn to insert to your system
+/// Add this macro to your Fn to insert to your system.
+///
+/// The function may take system parameters such as `Query<(Entity, &mut Square)>`,
+/// `Res<Player>` or `ResMut<Player>`. Their data is fetched from the host in one
+/// call before the system runs, and mutations are written back after it returns.
 #[proc_macro_attribute]
 pub fn system(args: TokenStream, input: TokenStream) -> TokenStream {
     // Parse the input function
@@ -28,10 +32,85 @@ pub fn system(args: TokenStream, input: TokenStream) -> TokenStream {
     let export_fn_name = quote::format_ident!("__mod_export_system_{}", fn_name);
     // Generate the get info function name
     let info_fn_name = quote::format_ident!("__mod_info_system_{}", fn_name);
+    // Generate the get access function name
+    let access_fn_name = quote::format_ident!("__mod_access_system_{}", fn_name);
 
     // Convert function name to string for SystemInfo
     let fn_name_str = export_fn_name.to_string();
 
+    // Collect the types of the system parameters
+    let mut param_types = Vec::new();
+    for input in &input_fn.sig.inputs {
+        match input {
+            FnArg::Typed(pat_type) => param_types.push(&*pat_type.ty),
+            FnArg::Receiver(receiver) => {
+                return syn::Error::new_spanned(receiver, "a system cannot take `self`")
+                    .to_compile_error()
+                    .into();
+            }
+        }
+    }
+
+    // Generate the body of the export function, and the access function for systems
+    // with parameters. Systems without it are treated as accessing the whole world.
+    let (run_system, access_fn) = if param_types.is_empty() {
+        let run_system = quote! {
+            // Call the original function
+            #fn_name();
+        };
+        (run_system, quote! {})
+    } else {
+        let param_idents: Vec<_> = (0..param_types.len())
+            .map(|i| quote::format_ident!("__param_{}", i))
+            .collect();
+        let run_system = quote! {
+            // Fetch the data of all parameters in one host call
+            let requests = vec![
+                #(<#param_types as bevy_modapi::SystemParam>::request()),*
+            ];
+            let mut data = bevy_modapi::param::fetch_params(&requests).into_iter();
+
+            // Build the parameters and run the system, skipping it if one is unavailable.
+            // The parameters built before are dropped in the closure, so their writes are
+            // queued before being discarded.
+            let mut run = || -> Option<()> {
+                #(
+                    let #param_idents =
+                        <#param_types as bevy_modapi::SystemParam>::from_data(data.next())?;
+                )*
+
+                // Call the original function
+                #fn_name(#(#param_idents),*);
+                Some(())
+            };
+
+            // Write back the mutations
+            match run() {
+                Some(()) => bevy_modapi::param::flush_writes(),
+                None => bevy_modapi::param::discard_writes(),
+            }
+        };
+        let access_fn = quote! {
+            // Get system access
+            #[unsafe(no_mangle)]
+            pub extern "C" fn #access_fn_name() -> *const bevy_modapi::HostModResult {
+                // Keep the serialized access alive for the host to read it
+                static SYSTEM_ACCESS: std::sync::OnceLock<(Vec<u8>, bevy_modapi::HostModResult)> =
+                    std::sync::OnceLock::new();
+
+                let (_, result) = SYSTEM_ACCESS.get_or_init(|| {
+                    let mut access = bevy_modapi::SystemAccess::default();
+                    #(<#param_types as bevy_modapi::SystemParam>::access(&mut access);)*
+                    let mut storage = Vec::new();
+                    let result = bevy_modapi::param::encode_access(&access, &mut storage);
+                    (storage, result)
+                });
+                result as *const bevy_modapi::HostModResult
+            }
+        };
+        (run_system, access_fn)
+    };
+
     // Generate the output tokens
     let expanded = quote! {
         // Keep the original function
@@ -40,10 +119,11 @@ pub fn system(args: TokenStream, input: TokenStream) -> TokenStream {
         // Generate the export function
         #[unsafe(no_mangle)]
         pub extern "C" fn #export_fn_name() {
-            // Call the original function
-            #fn_name();
+            #run_system
         }
 
+        #access_fn
+
         // Get system info
         #[unsafe(no_mangle)]
         pub extern "C" fn #info_fn_name() -> *const bevy_modapi::SystemInfo {
@@ -157,6 +237,24 @@ pub fn system_def(input: TokenStream) -> TokenStream {
             MOD_SYSTEM_NAMES.as_ptr() as *const u8
         }
 
+        // Set the minimum level of the logs sent to the host
+        #[unsafe(no_mangle)]
+        pub extern "C" fn __mod_set_log_level(level: u32) {
+            bevy_modapi::log::set_min_level(level);
+        }
+
+        // Get the formats of the exchanged data supported by the mod
+        #[unsafe(no_mangle)]
+        pub extern "C" fn __mod_formats() -> u32 {
+            bevy_modapi::format::supported_mask()
+        }
+
+        // Set the format of the exchanged data agreed with the host
+        #[unsafe(no_mangle)]
+        pub extern "C" fn __mod_set_format(format: u32) {
+            bevy_modapi::format::set_current(format);
+        }
+
         // Generate a function that returns the mod name (derived from the crate name)
         #[unsafe(no_mangle)]
         pub extern "C" fn __mod_get_name() -> *const u8 {
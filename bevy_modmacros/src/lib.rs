@@ -0,0 +1,307 @@
+//! Shared code of the macro crates
+//!
+//! The registration macros of the host (`bevy_modruntime_macros`) and of the mod SDK
+//! (`bevy_modsdk_macros`) must parse the same arguments and hash types the same way, or
+//! the schema of a type would never match its SDK mirror.
+
+use quote::quote;
+use syn::{Data, DeriveInput, Fields};
+
+/// Arguments of the registration macros, like `id = "square", namespaced`
+#[derive(Default)]
+pub struct RegistrationArgs {
+    /// Id of the type, the type name by default
+    pub id: Option<syn::LitStr>,
+    /// Whether the id is prefixed with the name of the crate, like `game::square`
+    pub namespaced: bool,
+    /// Registered instantiations of a generic type, with their ids
+    pub instances: Vec<(syn::Type, syn::LitStr)>,
+    /// Whether `namespaced` is accepted
+    allow_namespaced: bool,
+}
+
+/// A registered type
+pub struct Instance {
+    /// The concrete type
+    pub ty: syn::Type,
+    /// The id of the type
+    pub id: syn::LitStr,
+    /// Hash of the shape of the type
+    pub schema_hash: u64,
+}
+
+impl RegistrationArgs {
+    /// Arguments accepting `namespaced`, only the host knows the crate defining a type
+    pub fn with_namespaced() -> Self {
+        Self {
+            allow_namespaced: true,
+            ..Default::default()
+        }
+    }
+
+    /// Parse one argument
+    pub fn parse(&mut self, meta: syn::meta::ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("id") {
+            self.id = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if self.allow_namespaced && meta.path.is_ident("namespaced") {
+            self.namespaced = true;
+            Ok(())
+        } else if meta.path.is_ident("instance") {
+            let content;
+            syn::parenthesized!(content in meta.input);
+            let ty = content.parse()?;
+            content.parse::<syn::Token![=]>()?;
+            self.instances.push((ty, content.parse()?));
+            Ok(())
+        } else if self.allow_namespaced {
+            Err(meta.error(
+                "expected `id = \"...\"`, `namespaced` or `instance(Type<..> = \"...\")`",
+            ))
+        } else {
+            Err(meta.error("expected `id = \"...\"` or `instance(Type<..> = \"...\")`"))
+        }
+    }
+
+    /// Get the registered types, one per instance for generic types
+    pub fn instances(&self, input: &DeriveInput) -> syn::Result<Vec<Instance>> {
+        let name = &input.ident;
+        let schema = type_schema(input);
+
+        if input.generics.params.is_empty() {
+            if let Some((ty, _)) = self.instances.first() {
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    format!("`instance` is only for generic types, `{}` is not generic", name),
+                ));
+            }
+            return Ok(vec![Instance {
+                ty: syn::parse_quote!(#name),
+                id: self
+                    .id
+                    .clone()
+                    .unwrap_or_else(|| syn::LitStr::new(&name.to_string(), name.span())),
+                schema_hash: bevy_modtypes::schema_hash(&schema),
+            }]);
+        }
+
+        if let Some(id) = &self.id {
+            return Err(syn::Error::new_spanned(
+                id,
+                "generic types have an id per instance, use `instance(Type<..> = \"...\")`",
+            ));
+        }
+        if self.instances.is_empty() {
+            return Err(syn::Error::new_spanned(
+                &input.generics,
+                format!(
+                    "generic types are registered per instance, add `instance({}<..> = \"...\")`",
+                    name
+                ),
+            ));
+        }
+        self.instances
+            .iter()
+            .map(|(ty, id)| {
+                Ok(Instance {
+                    ty: ty.clone(),
+                    id: id.clone(),
+                    schema_hash: bevy_modtypes::schema_hash(&format!(
+                        "{}{}",
+                        schema,
+                        instance_schema(name, ty)?
+                    )),
+                })
+            })
+            .collect()
+    }
+
+    /// Get the expression of an id
+    pub fn id_tokens(&self, id: &syn::LitStr) -> proc_macro2::TokenStream {
+        if self.namespaced {
+            quote! { concat!(env!("CARGO_CRATE_NAME"), "::", #id) }
+        } else {
+            quote! { #id }
+        }
+    }
+}
+
+/// Describe the generic arguments of an instance of a type, like `<f32>`
+fn instance_schema(name: &syn::Ident, ty: &syn::Type) -> syn::Result<String> {
+    let segment = match ty {
+        syn::Type::Path(path) if path.qself.is_none() => path.path.segments.last(),
+        _ => None,
+    };
+    match segment {
+        Some(segment) if segment.ident == *name => {
+            let arguments = &segment.arguments;
+            Ok(bevy_modtypes::normalize_schema_type(&quote!(#arguments).to_string()))
+        }
+        _ => Err(syn::Error::new_spanned(
+            ty,
+            format!("expected an instance of `{}`, like `{}<..>`", name, name),
+        )),
+    }
+}
+
+/// Describe the shape of a type, hashed to check it between the host and the mods
+///
+/// Only the names of the field types are described, not their own fields, so a nested type
+/// changing its layout keeps the same schema.
+pub fn type_schema(input: &DeriveInput) -> String {
+    match &input.data {
+        Data::Struct(data) => fields_schema(&data.fields),
+        Data::Enum(data) => {
+            let variants: Vec<String> = data
+                .variants
+                .iter()
+                .map(|variant| format!("{}{}", variant.ident, fields_schema(&variant.fields)))
+                .collect();
+            format!("enum{{{}}}", variants.join(","))
+        }
+        Data::Union(_) => "union".to_string(),
+    }
+}
+
+/// Get the fields ignored by reflection but still serialized, like `speed` or `Variant.0`
+///
+/// Reflection cannot see these fields, so the SDK generator cannot define them.
+pub fn reflect_ignored_fields(input: &DeriveInput) -> Vec<String> {
+    let ignored = |fields: &Fields, prefix: &str| -> Vec<String> {
+        fields
+            .iter()
+            .enumerate()
+            .filter(|(_, field)| {
+                has_attribute_flag(field, "reflect", "ignore")
+                    && !has_attribute_flag(field, "serde", "skip")
+            })
+            .map(|(i, field)| match &field.ident {
+                Some(ident) => format!("{}{}", prefix, ident),
+                None => format!("{}{}", prefix, i),
+            })
+            .collect()
+    };
+    match &input.data {
+        Data::Struct(data) => ignored(&data.fields, ""),
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .flat_map(|variant| ignored(&variant.fields, &format!("{}.", variant.ident)))
+            .collect(),
+        Data::Union(_) => Vec::new(),
+    }
+}
+
+/// Whether a field has a flag in an attribute, like `ignore` in `#[reflect(ignore)]`
+fn has_attribute_flag(field: &syn::Field, attribute: &str, flag: &str) -> bool {
+    field.attrs.iter().any(|attr| {
+        attr.path().is_ident(attribute)
+            && attr.meta.require_list().is_ok_and(|list| {
+                list.tokens
+                    .to_string()
+                    .split(',')
+                    .any(|item| item.trim() == flag)
+            })
+    })
+}
+
+/// Describe the fields of a struct or variant, empty fields are all alike
+///
+/// Fields skipped by serde are left out, mods never see them.
+fn fields_schema(fields: &Fields) -> String {
+    let field_type = |field: &syn::Field| {
+        let ty = &field.ty;
+        bevy_modtypes::normalize_schema_type(&quote!(#ty).to_string())
+    };
+    let serialized = |field: &&syn::Field| !has_attribute_flag(field, "serde", "skip");
+    if fields.iter().filter(serialized).count() == 0 {
+        return ";".to_string();
+    }
+    match fields {
+        Fields::Named(fields) => {
+            let fields: Vec<String> = fields
+                .named
+                .iter()
+                .filter(serialized)
+                .map(|field| {
+                    let name = field.ident.as_ref().map(|ident| ident.to_string());
+                    format!("{}:{}", name.unwrap_or_default(), field_type(field))
+                })
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Fields::Unnamed(fields) => {
+            let fields: Vec<String> =
+                fields.unnamed.iter().filter(serialized).map(field_type).collect();
+            format!("({})", fields.join(","))
+        }
+        Fields::Unit => ";".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Get the schema hash of a type registered without arguments
+    fn schema_hash(input: DeriveInput) -> u64 {
+        let instances = RegistrationArgs::default().instances(&input).unwrap();
+        instances[0].schema_hash
+    }
+
+    #[test]
+    fn host_type_and_sdk_mirror_hash_alike() {
+        let host: DeriveInput = syn::parse_quote! {
+            pub struct Player {
+                pub position: bevy::math::Vec2,
+                pub name: std::string::String,
+                #[serde(skip)]
+                pub cache: Vec<u8>,
+            }
+        };
+        let sdk: DeriveInput = syn::parse_quote! {
+            pub struct Player {
+                pub position: Vec2,
+                pub name: String,
+            }
+        };
+        assert_eq!(schema_hash(host), schema_hash(sdk));
+    }
+
+    #[test]
+    fn changed_fields_change_the_hash() {
+        let before: DeriveInput = syn::parse_quote! {
+            pub struct Speed { pub value: f32 }
+        };
+        let after: DeriveInput = syn::parse_quote! {
+            pub struct Speed { pub value: f64 }
+        };
+        assert_ne!(schema_hash(before), schema_hash(after));
+    }
+
+    #[test]
+    fn instances_hash_their_arguments() {
+        let input: DeriveInput = syn::parse_quote! {
+            pub struct Wrapper<T>(pub T);
+        };
+        let mut args = RegistrationArgs::default();
+        args.instances.push((syn::parse_quote!(Wrapper<f32>), syn::parse_quote!("a")));
+        args.instances.push((syn::parse_quote!(Wrapper<u32>), syn::parse_quote!("b")));
+        let instances = args.instances(&input).unwrap();
+        assert_ne!(instances[0].schema_hash, instances[1].schema_hash);
+    }
+
+    #[test]
+    fn reflect_ignored_fields_skip_serde_skipped() {
+        let input: DeriveInput = syn::parse_quote! {
+            pub struct Player {
+                #[reflect(ignore)]
+                pub speed: f32,
+                #[reflect(ignore)]
+                #[serde(skip)]
+                pub cache: Vec<u8>,
+            }
+        };
+        assert_eq!(reflect_ignored_fields(&input), vec!["speed".to_string()]);
+    }
+}
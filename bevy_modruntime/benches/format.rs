@@ -0,0 +1,87 @@
+//! Cost per entity of a mod query in each serialization format
+//!
+//! Measures the host serializing the components of the matched entities, then the mod
+//! deserializing them, without the copy into wasm memory. Enable the `postcard` and
+//! `msgpack` features to compare all formats:
+//!
+//! ```sh
+//! cargo bench -p bevy_modruntime --features postcard,msgpack
+//! ```
+
+use bevy::prelude::*;
+use bevy_modruntime::query::query_components_from_world;
+use bevy_modruntime::{Format, mod_component};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+
+#[mod_component(id = "bench::body")]
+#[derive(Component, Clone)]
+pub struct Body {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub velocity: Vec3,
+    pub name: String,
+}
+
+#[mod_component(id = "bench::health")]
+#[derive(Component, Clone)]
+pub struct Health(pub u32);
+
+/// Number of entities matched by the query
+const ENTITIES: usize = 1000;
+
+/// Create a world with entities matched by the query
+fn setup_world() -> World {
+    let mut world = World::new();
+    world.init_resource::<AppTypeRegistry>();
+    for i in 0..ENTITIES {
+        world.spawn((
+            Body {
+                translation: Vec3::new(i as f32, 1.0, 2.0),
+                rotation: Quat::from_rotation_z(i as f32),
+                velocity: Vec3::X,
+                name: format!("body {}", i),
+            },
+            Health(i as u32),
+        ));
+    }
+    world
+}
+
+/// Decode a query result like a mod does
+fn decode_query(format: Format, data: &[u8]) -> Vec<(Body, Health)> {
+    let entities: Vec<Vec<Vec<u8>>> = format.decode(data).unwrap();
+    entities
+        .iter()
+        .map(|components| {
+            (
+                format.decode(&components[0]).unwrap(),
+                format.decode(&components[1]).unwrap(),
+            )
+        })
+        .collect()
+}
+
+fn bench_query(c: &mut Criterion) {
+    let world = setup_world();
+    let world = world.as_unsafe_world_cell_readonly();
+    let ids = vec!["bench::body".to_string(), "bench::health".to_string()];
+
+    let mut group = c.benchmark_group("query");
+    group.throughput(Throughput::Elements(ENTITIES as u64));
+    for format in Format::ALL.into_iter().filter(|format| format.is_supported()) {
+        let data = query_components_from_world(&world, &ids, format).unwrap();
+        println!("{}: {} bytes per entity", format, data.len() / ENTITIES);
+
+        group.bench_function(BenchmarkId::new("host", format), |b| {
+            b.iter(|| query_components_from_world(&world, black_box(&ids), format))
+        });
+        group.bench_function(BenchmarkId::new("mod", format), |b| {
+            b.iter(|| decode_query(format, black_box(&data)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_query);
+criterion_main!(benches);
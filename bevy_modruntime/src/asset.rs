@@ -1,24 +1,459 @@
 use crate::ModState;
+use crate::utils::write_host_result;
+use crate::param::DeferredWrite;
+use crate::package::{MOD_ASSET_SOURCE, ModPackages};
 use anyhow::anyhow;
-use bevy::log::*;
+use bevy::asset::{AssetLoader, AssetPath, LoadContext, LoadState, UntypedAssetId, io::Reader};
+use bevy::gltf::GltfAssetLabel;
+use bevy::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Component, Path, PathBuf};
 use wasmtime::{Caller, Memory, Result};
 
 pub struct AssetInfo {
     pub mod_name: String,
     pub asset_type: String,
+    /// Path of the asset in the mod sources
+    pub asset_path: String,
     pub asset_data: Vec<u8>,
 }
 
+/// Asset types with built-in handling
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModAssetType {
+    /// An `Image`
+    Image,
+    /// An `AudioSource`
+    Audio,
+    /// A [`TextAsset`]
+    Text,
+    /// A `Font`
+    Font,
+    /// A `DynamicScene`
+    Scene,
+    /// The first `Scene` of a glTF file
+    Gltf,
+}
+
+impl ModAssetType {
+    /// Parse the asset type used in `asset_def!`
+    pub fn parse(asset_type: &str) -> Option<Self> {
+        match asset_type {
+            "image" => Some(ModAssetType::Image),
+            "audio" => Some(ModAssetType::Audio),
+            "text" => Some(ModAssetType::Text),
+            "font" => Some(ModAssetType::Font),
+            "scene" => Some(ModAssetType::Scene),
+            "gltf" => Some(ModAssetType::Gltf),
+            _ => None,
+        }
+    }
+}
+
+/// A text asset
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct TextAsset(pub String);
+
+/// Asset loader for [`TextAsset`]
+#[derive(Default)]
+pub struct TextAssetLoader;
+
+impl AssetLoader for TextAssetLoader {
+    type Asset = TextAsset;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<TextAsset, std::io::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let text = String::from_utf8(bytes)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        Ok(TextAsset(text))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["txt"]
+    }
+}
+
+/// The raw bytes of a file, read by mods
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct BytesAsset(pub Vec<u8>);
+
+/// Asset loader for [`BytesAsset`]
+///
+/// It has no extensions, and is only used when loading a `BytesAsset` explicitly.
+#[derive(Default)]
+pub struct BytesAssetLoader;
+
+impl AssetLoader for BytesAssetLoader {
+    type Asset = BytesAsset;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<BytesAsset, std::io::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(BytesAsset(bytes))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &[]
+    }
+}
+
+/// Get the id of an asset of a mod, `<mod name>:<path>`
+///
+/// The path is relative to the asset folder of the mod, so ids of different mods never
+/// collide.
+pub fn mod_asset_id(mod_name: &str, path: &str) -> String {
+    let relative_path = normalize_asset_path(path);
+    format!(
+        "{}:{}",
+        mod_name,
+        relative_path.to_string_lossy().replace('\\', "/")
+    )
+}
+
+/// Resource keeping the assets of mods alive, by mod and asset id
+///
+/// Every mod holds strong handles to the assets it defined or loaded. They are released
+/// when the mod is unloaded or reloaded, and the assets are freed once no mod holds them.
+#[derive(Resource, Default)]
+pub struct ModAssetHandles {
+    /// Handles of each mod, by asset id
+    mods: HashMap<String, HashMap<String, UntypedHandle>>,
+    /// Assets defined by mods, by hash of their type and data
+    contents: HashMap<u64, UntypedAssetId>,
+}
+
+impl ModAssetHandles {
+    /// Get the handle of an asset of a mod
+    pub fn get<A: Asset>(&self, id: &str) -> Option<Handle<A>> {
+        self.get_handle(id)
+            .and_then(|handle| handle.clone().try_typed::<A>().ok())
+    }
+
+    /// Get the untyped id of an asset of a mod
+    pub fn get_untyped(&self, id: &str) -> Option<UntypedAssetId> {
+        self.get_handle(id).map(|handle| handle.id())
+    }
+
+    /// Get the handle of an asset by id, looked up in the table of the mod owning the id
+    ///
+    /// Both mod names and asset paths may contain `:`, so the owner is the mod whose name
+    /// prefixes the id rather than the text before the first `:`.
+    fn get_handle(&self, id: &str) -> Option<&UntypedHandle> {
+        self.mods.iter().find_map(|(mod_name, handles)| {
+            id.strip_prefix(mod_name.as_str())?.strip_prefix(':')?;
+            handles.get(id)
+        })
+    }
+
+    /// Get the handle of an asset held by a mod under any id, like a host asset path
+    pub(crate) fn get_mod_handle(&self, mod_name: &str, id: &str) -> Option<&UntypedHandle> {
+        self.mods.get(mod_name)?.get(id)
+    }
+
+    /// Keep an asset alive for a mod
+    pub(crate) fn insert(&mut self, mod_name: &str, id: String, handle: UntypedHandle) {
+        self.mods
+            .entry(mod_name.to_string())
+            .or_default()
+            .insert(id, handle);
+    }
+
+    /// Whether a mod holds an asset
+    pub fn contains(&self, id: &str) -> bool {
+        self.get_handle(id).is_some()
+    }
+
+    /// Find a live asset defined with the same content hash
+    pub(crate) fn find_content(&self, hash: u64) -> Option<UntypedHandle> {
+        let asset_id = self.contents.get(&hash)?;
+        self.mods
+            .values()
+            .flat_map(|handles| handles.values())
+            .find(|handle| handle.id() == *asset_id)
+            .cloned()
+    }
+
+    /// Get the content hash of an asset defined by a mod
+    pub(crate) fn content_hash(&self, asset_id: UntypedAssetId) -> Option<u64> {
+        self.contents
+            .iter()
+            .find(|(_, id)| **id == asset_id)
+            .map(|(hash, _)| *hash)
+    }
+
+    /// Remember the content hash of an asset, replacing its previous content
+    pub(crate) fn insert_content(&mut self, hash: u64, asset_id: UntypedAssetId) {
+        self.contents.retain(|_, id| *id != asset_id);
+        self.contents.insert(hash, asset_id);
+    }
+
+    /// Release the handles held by a mod
+    ///
+    /// The assets are freed by the `AssetServer` once no other mod holds them.
+    pub fn release_mod(&mut self, mod_name: &str) {
+        let Some(handles) = self.mods.remove(mod_name) else {
+            return;
+        };
+        info!("Released {} assets of mod '{}'", handles.len(), mod_name);
+
+        // Forget the contents no mod holds anymore
+        let live: HashSet<UntypedAssetId> = self
+            .mods
+            .values()
+            .flat_map(|handles| handles.values())
+            .map(|handle| handle.id())
+            .collect();
+        self.contents.retain(|_, asset_id| live.contains(asset_id));
+    }
+
+    /// Iterate over the ids of the assets held by a mod
+    pub fn mod_ids(&self, mod_name: &str) -> impl Iterator<Item = &str> {
+        self.mods
+            .get(mod_name)
+            .into_iter()
+            .flat_map(|handles| handles.keys())
+            .map(|id| id.as_str())
+    }
+
+    /// Iterate over the ids of all assets held by mods
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.mods
+            .values()
+            .flat_map(|handles| handles.keys())
+            .map(|id| id.as_str())
+    }
+}
+
+/// Built-in handling of assets defined by mods
+///
+/// The asset data is mounted in the `mod://` asset source, under the folder of the mod,
+/// and loaded through the `AssetServer`. The returned asset id is `<mod name>:<path>`,
+/// so it stays the same between runs. Assets with the same type and data are loaded
+/// once and shared, even between mods.
+/// Returns an empty id for unsupported asset types.
+pub fn new_builtin_asset(world: &mut World, asset_info: AssetInfo) -> String {
+    let Some(asset_type) = ModAssetType::parse(&asset_info.asset_type) else {
+        error!(
+            "Unsupported asset type '{}' defined by mod '{}'",
+            asset_info.asset_type, asset_info.mod_name
+        );
+        return String::new();
+    };
+
+    let Some(asset_server) = world.get_resource::<AssetServer>().cloned() else {
+        error!("AssetPlugin is required to define assets in mods");
+        return String::new();
+    };
+    if asset_server.get_source(MOD_ASSET_SOURCE).is_err() {
+        error!("WasmModPlugin must be added before AssetPlugin to define assets in mods");
+        return String::new();
+    }
+
+    let asset_id = mod_asset_id(&asset_info.mod_name, &asset_info.asset_path);
+    let hash = {
+        let mut hasher = DefaultHasher::new();
+        asset_type.hash(&mut hasher);
+        asset_info.asset_data.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    // Share the asset if the same data was already defined
+    let mut r_handles = world.resource_mut::<ModAssetHandles>();
+    if let Some(handle) = r_handles.find_content(hash) {
+        r_handles.insert(&asset_info.mod_name, asset_id.clone(), handle);
+        return asset_id;
+    }
+
+    // Mount the data in the folder of the mod
+    let relative_path = normalize_asset_path(&asset_info.asset_path);
+    world
+        .resource::<ModPackages>()
+        .insert_asset(&asset_info.mod_name, &relative_path, asset_info.asset_data);
+    let path = format!(
+        "{}://{}/{}",
+        MOD_ASSET_SOURCE,
+        asset_info.mod_name,
+        relative_path.to_string_lossy()
+    );
+
+    let Some(handle) = load_typed_asset(world, &asset_server, asset_type, &path) else {
+        return String::new();
+    };
+
+    // Redefining an asset with new data while it is still alive picks up the new data
+    let mut r_handles = world.resource_mut::<ModAssetHandles>();
+    if r_handles.content_hash(handle.id()).is_some() {
+        asset_server.reload(path);
+    }
+    r_handles.insert_content(hash, handle.id());
+    r_handles.insert(&asset_info.mod_name, asset_id.clone(), handle);
+
+    asset_id
+}
+
+/// Load an asset through the `AssetServer` as the Bevy asset of its type
+///
+/// Returns `None` if the asset type is not registered in the app, the `AssetServer` panics
+/// when loading it.
+fn load_typed_asset(
+    world: &World,
+    asset_server: &AssetServer,
+    asset_type: ModAssetType,
+    path: &str,
+) -> Option<UntypedHandle> {
+    let path = path.to_string();
+    match asset_type {
+        ModAssetType::Image => load_registered_asset::<Image>(world, asset_server, path),
+        #[cfg(feature = "audio")]
+        ModAssetType::Audio => load_registered_asset::<AudioSource>(world, asset_server, path),
+        #[cfg(not(feature = "audio"))]
+        ModAssetType::Audio => {
+            error!("The audio feature is required to load audio assets for mods");
+            None
+        }
+        ModAssetType::Text => load_registered_asset::<TextAsset>(world, asset_server, path),
+        ModAssetType::Font => load_registered_asset::<Font>(world, asset_server, path),
+        ModAssetType::Scene => load_registered_asset::<DynamicScene>(world, asset_server, path),
+        ModAssetType::Gltf => load_registered_asset::<Scene>(
+            world,
+            asset_server,
+            GltfAssetLabel::Scene(0).from_asset(path),
+        ),
+    }
+}
+
+/// Load an asset through the `AssetServer`, if its type is registered in the app
+fn load_registered_asset<A: Asset>(
+    world: &World,
+    asset_server: &AssetServer,
+    path: impl Into<AssetPath<'static>>,
+) -> Option<UntypedHandle> {
+    let path = path.into();
+    if !is_asset_type_registered::<A>(world) {
+        error!(
+            "Asset type {} is not registered, add its plugin to load '{}' for mods",
+            std::any::type_name::<A>(),
+            path
+        );
+        return None;
+    }
+    Some(asset_server.load::<A>(path).untyped())
+}
+
+/// Whether the app registered an asset type, so the `AssetServer` can load it
+fn is_asset_type_registered<A: Asset>(world: &World) -> bool {
+    world.contains_resource::<Assets<A>>()
+}
+
+/// Get the asset path of a file in the asset folder of a mod
+///
+/// The assets of a mod package are in the `mod://` source, and the assets of other mods
+/// in the mod asset directory of the default source.
+pub fn mod_asset_path(world: &World, mod_name: &str, path: &str) -> String {
+    let relative_path = normalize_asset_path(path);
+    let is_package = world
+        .get_resource::<ModPackages>()
+        .is_some_and(|packages| packages.is_package(mod_name));
+    if is_package {
+        return format!(
+            "{}://{}/{}",
+            MOD_ASSET_SOURCE,
+            mod_name,
+            relative_path.to_string_lossy()
+        );
+    }
+
+    let asset_dir = world
+        .get_resource::<crate::WasmModPlugin>()
+        .map(|plugin| plugin.mod_asset_dir.clone())
+        .unwrap_or_default();
+    Path::new(&asset_dir)
+        .join(mod_name)
+        .join(relative_path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Turn the source path of an asset into a path relative to the folder of its mod
+fn normalize_asset_path(asset_path: &str) -> PathBuf {
+    Path::new(asset_path)
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Get a handle from an asset id sent by a mod
+///
+/// The id is either a `<mod name>:<path>` id of an asset held by a mod, or an asset path
+/// loaded with the `AssetServer`. An empty id gives the default handle.
+pub fn resolve_asset_handle<A: Asset>(world: &World, id: &str) -> Handle<A> {
+    if id.is_empty() {
+        return Handle::default();
+    }
+    if let Some(handle) = world
+        .get_resource::<ModAssetHandles>()
+        .and_then(|handles| handles.get::<A>(id))
+    {
+        return handle;
+    }
+    if !is_asset_type_registered::<A>(world) {
+        error!(
+            "Asset type {} is not registered, add its plugin to load '{}' for mods",
+            std::any::type_name::<A>(),
+            id
+        );
+        return Handle::default();
+    }
+    match world.get_resource::<AssetServer>() {
+        Some(asset_server) => asset_server.load(id.to_string()),
+        None => {
+            error!("AssetPlugin is required to load asset '{}'", id);
+            Handle::default()
+        }
+    }
+}
+
+/// Get the asset id sent to mods for a handle, an empty id if the asset has no path
+pub fn asset_handle_id<A: Asset>(handle: &Handle<A>) -> String {
+    handle
+        .path()
+        .map(|path| path.to_string())
+        .unwrap_or_default()
+}
+
 /// Host function to handle asset definition from mods
 ///
-/// This function receives the mod name, asset type, and asset data from the mod
+/// This function receives the mod name, asset type, asset path and asset data from the mod
 /// and stores them for later use.
+#[allow(clippy::too_many_arguments)]
 pub fn host_handle_define_asset(
     mut caller: Caller<'_, ModState>,
     mod_name_ptr: i32,
     mod_name_len: i32,
     asset_type_ptr: i32,
     asset_type_len: i32,
+    asset_path_ptr: i32,
+    asset_path_len: i32,
     asset_data_ptr: i32,
     asset_data_len: i32,
     asset_id_ptr: i32,
@@ -41,6 +476,14 @@ pub fn host_handle_define_asset(
         ));
     }
 
+    if asset_path_ptr <= 0 || asset_path_len <= 0 {
+        return Err(anyhow!(
+            "Invalid asset path parameters: ptr={}, len={}",
+            asset_path_ptr,
+            asset_path_len
+        ));
+    }
+
     if asset_data_ptr <= 0 || asset_data_len < 0 {
         return Err(anyhow!(
             "Invalid asset data parameters: ptr={}, len={}",
@@ -74,6 +517,14 @@ pub fn host_handle_define_asset(
         asset_type_len as u32,
     )?;
 
+    // Read the asset path from memory
+    let asset_path = read_string_from_memory(
+        &memory,
+        &mut caller,
+        asset_path_ptr as u32,
+        asset_path_len as u32,
+    )?;
+
     // Read the asset data from memory
     let asset_data = read_bytes_from_memory(
         &memory,
@@ -85,9 +536,16 @@ pub fn host_handle_define_asset(
     let asset_info = AssetInfo {
         mod_name,
         asset_type,
+        asset_path,
         asset_data,
     };
 
+    // The asset function needs the whole world, which is not available in parallel
+    if caller.data().is_deferred() {
+        error!("Assets cannot be defined by systems running in parallel with other mods");
+        return Ok(0);
+    }
+
     let world = match caller.data().get_world() {
         Some(world) => world,
         None => {
@@ -125,15 +583,255 @@ pub fn host_handle_define_asset(
     Ok(asset_id.len() as u32)
 }
 
+/// Host function to load an asset from the asset folder of the calling mod
+///
+/// The asset is loaded in the background by the `AssetServer`. The asset ID is written
+/// immediately, and the load state can be polled with `__mod_asset_load_state`.
+/// Returns the length of the asset ID, 0 on failure or if the asset type is not registered.
+pub fn host_handle_load_asset(
+    mut caller: Caller<'_, ModState>,
+    asset_type_ptr: i32,
+    asset_type_len: i32,
+    asset_path_ptr: i32,
+    asset_path_len: i32,
+    asset_id_ptr: i32,
+    asset_id_len: i32,
+) -> Result<u32> {
+    let memory = match caller.get_export("memory") {
+        Some(export) => match export.into_memory() {
+            Some(memory) => memory,
+            None => return Err(anyhow!("Failed to get memory export")),
+        },
+        None => return Err(anyhow!("Missing memory export")),
+    };
+
+    let asset_type = read_string_from_memory(
+        &memory,
+        &mut caller,
+        asset_type_ptr as u32,
+        asset_type_len as u32,
+    )?;
+    let asset_path = read_string_from_memory(
+        &memory,
+        &mut caller,
+        asset_path_ptr as u32,
+        asset_path_len as u32,
+    )?;
+
+    let mod_name = caller.data().get_mod_name().unwrap_or_default().to_string();
+    let Some(asset_type) = ModAssetType::parse(&asset_type) else {
+        error!(
+            "Unsupported asset type '{}' loaded by mod '{}'",
+            asset_type, mod_name
+        );
+        return Ok(0);
+    };
+
+    let Some(world) = caller.data().get_world() else {
+        error!("get bevy world faild while loading asset");
+        return Ok(0);
+    };
+    // Only shared access is needed, so this also works while running in parallel
+    let world_ref = unsafe { world.world() };
+    let Some(asset_server) = world_ref.get_resource::<AssetServer>() else {
+        error!("AssetPlugin is required to load assets for mods");
+        return Ok(0);
+    };
+
+    let path = mod_asset_path(world_ref, &mod_name, &asset_path);
+    let Some(handle) = load_typed_asset(world_ref, asset_server, asset_type, &path) else {
+        return Ok(0);
+    };
+    let asset_id = mod_asset_id(&mod_name, &asset_path);
+
+    // Keep the asset alive for the mod
+    if caller.data().is_deferred() {
+        caller.data_mut().push_deferred_write(DeferredWrite::Asset {
+            mod_name,
+            id: asset_id.clone(),
+            handle,
+        });
+    } else {
+        let world_mut = unsafe { world.world_mut() };
+        world_mut
+            .resource_mut::<ModAssetHandles>()
+            .insert(&mod_name, asset_id.clone(), handle);
+    }
+
+    // Write the asset ID to the WASM memory
+    let asset_id_bytes = asset_id.as_bytes();
+    let write_len = std::cmp::min(asset_id_bytes.len(), asset_id_len.max(0) as usize);
+    memory.write(&mut caller, asset_id_ptr as usize, &asset_id_bytes[..write_len])?;
+
+    Ok(asset_id.len() as u32)
+}
+
+/// Host function to get the load state of an asset
+///
+/// Returns 0 if not loaded, 1 while loading, 2 once loaded and 3 if loading failed.
+pub fn host_handle_asset_load_state(
+    mut caller: Caller<'_, ModState>,
+    asset_id_ptr: i32,
+    asset_id_len: i32,
+) -> Result<u32> {
+    let memory = match caller.get_export("memory") {
+        Some(export) => match export.into_memory() {
+            Some(memory) => memory,
+            None => return Err(anyhow!("Failed to get memory export")),
+        },
+        None => return Err(anyhow!("Missing memory export")),
+    };
+    let asset_id = read_string_from_memory(
+        &memory,
+        &mut caller,
+        asset_id_ptr as u32,
+        asset_id_len as u32,
+    )?;
+
+    let Some(world) = caller.data().get_world() else {
+        error!("get bevy world faild while getting asset load state");
+        return Ok(0);
+    };
+    let world_ref = unsafe { world.world() };
+    let Some(asset_server) = world_ref.get_resource::<AssetServer>() else {
+        return Ok(0);
+    };
+
+    let id = world_ref
+        .get_resource::<ModAssetHandles>()
+        .and_then(|handles| handles.get_untyped(&asset_id))
+        .or_else(|| {
+            asset_server
+                .get_handle_untyped(&asset_id)
+                .map(|handle| handle.id())
+        });
+    let state = match id.and_then(|id| asset_server.get_load_state(id)) {
+        None | Some(LoadState::NotLoaded) => 0,
+        Some(LoadState::Loading) => 1,
+        Some(LoadState::Loaded) => 2,
+        Some(LoadState::Failed(_)) => 3,
+    };
+
+    Ok(state)
+}
+
+/// Host function to read the bytes of an asset
+///
+/// Mods can read the `TextAsset`s and `BytesAsset`s they hold, and the host assets their
+/// permissions allow. A host asset path is loaded as a `BytesAsset` on first read.
+/// Returns the length of the data written to `result_ptr`, 0 while the asset is loading
+/// or if it cannot be read.
+pub fn host_handle_read_asset(
+    mut caller: Caller<'_, ModState>,
+    asset_id_ptr: i32,
+    asset_id_len: i32,
+    result_ptr: i32,
+) -> Result<i32> {
+    let memory = match caller.get_export("memory") {
+        Some(export) => match export.into_memory() {
+            Some(memory) => memory,
+            None => return Err(anyhow!("Failed to get memory export")),
+        },
+        None => return Err(anyhow!("Missing memory export")),
+    };
+    let asset_id = read_string_from_memory(
+        &memory,
+        &mut caller,
+        asset_id_ptr as u32,
+        asset_id_len as u32,
+    )?;
+
+    // Mods read their own assets, and the others they are allowed to
+    let mod_name = caller.data().get_mod_name().unwrap_or_default().to_string();
+    let own_path = asset_id
+        .strip_prefix(mod_name.as_str())
+        .and_then(|rest| rest.strip_prefix(':'))
+        .map(|path| path.to_string());
+    if own_path.is_none() && !caller.data().permissions().can_read_asset(&asset_id) {
+        error!(
+            "Mod '{}' is not allowed to read asset '{}'",
+            mod_name, asset_id
+        );
+        return Ok(0);
+    }
+
+    let Some(world) = caller.data().get_world() else {
+        error!("get bevy world faild while reading asset");
+        return Ok(0);
+    };
+    // Only shared access is needed, so this also works while running in parallel
+    let world_ref = unsafe { world.world() };
+    let Some(asset_server) = world_ref.get_resource::<AssetServer>() else {
+        error!("AssetPlugin is required to read assets in mods");
+        return Ok(0);
+    };
+
+    // Read the asset if a mod holds it already
+    let held = world_ref.get_resource::<ModAssetHandles>().and_then(|handles| {
+        handles
+            .get_untyped(&asset_id)
+            .or_else(|| handles.get_mod_handle(&mod_name, &asset_id).map(|handle| handle.id()))
+    });
+    if let Some(id) = held {
+        return Ok(match read_asset_bytes(world_ref, id) {
+            Some(data) => write_host_result(&mut caller, &memory, &data, result_ptr),
+            None => 0,
+        });
+    }
+
+    // Otherwise load the file, and keep it alive for the mod
+    let path = match &own_path {
+        Some(path) => mod_asset_path(world_ref, &mod_name, path),
+        None => asset_id.clone(),
+    };
+    let handle = asset_server.load::<BytesAsset>(path).untyped();
+    let data = read_asset_bytes(world_ref, handle.id());
+    if caller.data().is_deferred() {
+        caller.data_mut().push_deferred_write(DeferredWrite::Asset {
+            mod_name,
+            id: asset_id,
+            handle,
+        });
+    } else {
+        let world_mut = unsafe { world.world_mut() };
+        world_mut
+            .resource_mut::<ModAssetHandles>()
+            .insert(&mod_name, asset_id, handle);
+    }
+
+    Ok(match data {
+        Some(data) => write_host_result(&mut caller, &memory, &data, result_ptr),
+        None => 0,
+    })
+}
+
+/// Get the bytes of a loaded asset readable by mods
+fn read_asset_bytes(world: &World, id: UntypedAssetId) -> Option<Vec<u8>> {
+    if let Ok(id) = id.try_typed::<BytesAsset>() {
+        return world
+            .get_resource::<Assets<BytesAsset>>()?
+            .get(id)
+            .map(|asset| asset.0.clone());
+    }
+    if let Ok(id) = id.try_typed::<TextAsset>() {
+        return world
+            .get_resource::<Assets<TextAsset>>()?
+            .get(id)
+            .map(|asset| asset.0.clone().into_bytes());
+    }
+    error!("Mods can only read text and bytes assets");
+    None
+}
+
 /// Helper function to read a string from WASM memory
-fn read_string_from_memory<T>(
+pub(crate) fn read_string_from_memory<T>(
     memory: &Memory,
     caller: &mut Caller<'_, T>,
     ptr: u32,
     len: u32,
 ) -> Result<String> {
     let mut buffer = vec![0u8; len as usize];
-    memory.read(&caller, ptr as usize, &mut buffer)?;
+    memory.read(&*caller, ptr as usize, &mut buffer)?;
     Ok(String::from_utf8(buffer)?)
 }
 
@@ -145,6 +843,6 @@ fn read_bytes_from_memory<T>(
     len: u32,
 ) -> Result<Vec<u8>> {
     let mut buffer = vec![0u8; len as usize];
-    memory.read(&caller, ptr as usize, &mut buffer)?;
+    memory.read(&*caller, ptr as usize, &mut buffer)?;
     Ok(buffer)
 }
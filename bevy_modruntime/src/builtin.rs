@@ -0,0 +1,338 @@
+//! Built-in components and resources
+//!
+//! Registrations for core Bevy types, so basic gameplay mods work without registrations in
+//! the host: `Transform`, `GlobalTransform`, `Name`, `Visibility`, `ChildOf`, `Children`
+//! and the `Time` resource. Mods get matching types from `bevy_modapi`.
+//!
+//! Bevy components that hold asset handles are registered too, so mods can attach the
+//! assets they define to entities. Handles are sent to mods as asset ids, see
+//! [`resolve_asset_handle`] and [`asset_handle_id`].
+//!
+//! The mod data of built-in types does not follow the layout of the Bevy types, so their
+//! schemas are not checked. Types computed by Bevy, like `GlobalTransform`, `Children` and
+//! `Time`, are read-only for mods, writing them fails with `HostError::ReadOnly`.
+
+use crate::asset::{asset_handle_id, resolve_asset_handle};
+use crate::component::ComponentRegistration;
+use crate::resource::ResourceRegistration;
+use bevy::ecs::world::EntityWorldMut;
+use bevy::prelude::*;
+use bevy::reflect::TypeRegistry;
+use bevy_modtypes::{Format, FormatError, HostError};
+use serde::{Deserialize, Serialize};
+use std::any::{Any, TypeId};
+
+/// Serialize the data of a built-in component
+fn encode<T: Serialize>(data: &T, format: Format) -> Vec<u8> {
+    match format.encode(data) {
+        Ok(data) => data,
+        Err(e) => {
+            error!(
+                "Failed to serialize built-in type {}: {}",
+                std::any::type_name::<T>(),
+                e
+            );
+            Vec::new()
+        }
+    }
+}
+
+/// Deserialize the data of a built-in component
+fn decode<T: for<'de> Deserialize<'de> + 'static>(
+    data: &[u8],
+    format: Format,
+) -> Result<Box<dyn Any>, FormatError> {
+    Ok(Box::new(format.decode::<T>(data)?))
+}
+
+/// Get the deserialized data of a built-in component
+fn downcast<T: 'static>(data: Box<dyn Any>) -> Result<T, HostError> {
+    data.downcast::<T>()
+        .map(|data| *data)
+        .map_err(|_| HostError::InvalidData)
+}
+
+/// Mod data of a `Transform` or `GlobalTransform`
+#[derive(Serialize, Deserialize)]
+struct TransformData {
+    translation: [f32; 3],
+    rotation: [f32; 4],
+    scale: [f32; 3],
+}
+
+impl From<Transform> for TransformData {
+    fn from(transform: Transform) -> Self {
+        Self {
+            translation: transform.translation.to_array(),
+            rotation: transform.rotation.to_array(),
+            scale: transform.scale.to_array(),
+        }
+    }
+}
+
+#[linkme::distributed_slice(crate::COMPONENT_REGISTRY)]
+static TRANSFORM_REGISTRATION: ComponentRegistration = ComponentRegistration {
+    id: "bevy::Transform",
+    schema_hash: 0,
+    serialize_fn: |component, format| {
+        let transform = unsafe { component.deref::<Transform>() };
+        encode(&TransformData::from(*transform), format)
+    },
+    deserialize_fn: decode::<TransformData>,
+    get_type_id: TypeId::of::<Transform>,
+    type_name: std::any::type_name::<Transform>,
+    reg_fn: |registry: &mut TypeRegistry| registry.register::<Transform>(),
+    insert_fn: |entity: &mut EntityWorldMut, component: Box<dyn Any>| {
+        let data = downcast::<TransformData>(component)?;
+        entity.insert(Transform {
+            translation: Vec3::from_array(data.translation),
+            rotation: Quat::from_array(data.rotation),
+            scale: Vec3::from_array(data.scale),
+        });
+        Ok(())
+    },
+};
+
+#[linkme::distributed_slice(crate::COMPONENT_REGISTRY)]
+static GLOBAL_TRANSFORM_REGISTRATION: ComponentRegistration = ComponentRegistration {
+    id: "bevy::GlobalTransform",
+    schema_hash: 0,
+    serialize_fn: |component, format| {
+        let transform = unsafe { component.deref::<GlobalTransform>() };
+        encode(&TransformData::from(transform.compute_transform()), format)
+    },
+    deserialize_fn: decode::<TransformData>,
+    get_type_id: TypeId::of::<GlobalTransform>,
+    type_name: std::any::type_name::<GlobalTransform>,
+    reg_fn: |registry: &mut TypeRegistry| registry.register::<GlobalTransform>(),
+    insert_fn: |_, _| Err(HostError::ReadOnly),
+};
+
+#[linkme::distributed_slice(crate::COMPONENT_REGISTRY)]
+static NAME_REGISTRATION: ComponentRegistration = ComponentRegistration {
+    id: "bevy::Name",
+    schema_hash: 0,
+    serialize_fn: |component, format| {
+        let name = unsafe { component.deref::<Name>() };
+        encode(&name.as_str(), format)
+    },
+    deserialize_fn: decode::<String>,
+    get_type_id: TypeId::of::<Name>,
+    type_name: std::any::type_name::<Name>,
+    reg_fn: |registry: &mut TypeRegistry| registry.register::<Name>(),
+    insert_fn: |entity: &mut EntityWorldMut, component: Box<dyn Any>| {
+        entity.insert(Name::new(downcast::<String>(component)?));
+        Ok(())
+    },
+};
+
+/// Mod data of a `Visibility`
+#[derive(Serialize, Deserialize)]
+enum VisibilityData {
+    Inherited,
+    Hidden,
+    Visible,
+}
+
+#[linkme::distributed_slice(crate::COMPONENT_REGISTRY)]
+static VISIBILITY_REGISTRATION: ComponentRegistration = ComponentRegistration {
+    id: "bevy::Visibility",
+    schema_hash: 0,
+    serialize_fn: |component, format| {
+        let visibility = match unsafe { component.deref::<Visibility>() } {
+            Visibility::Inherited => VisibilityData::Inherited,
+            Visibility::Hidden => VisibilityData::Hidden,
+            Visibility::Visible => VisibilityData::Visible,
+        };
+        encode(&visibility, format)
+    },
+    deserialize_fn: decode::<VisibilityData>,
+    get_type_id: TypeId::of::<Visibility>,
+    type_name: std::any::type_name::<Visibility>,
+    reg_fn: |registry: &mut TypeRegistry| registry.register::<Visibility>(),
+    insert_fn: |entity: &mut EntityWorldMut, component: Box<dyn Any>| {
+        entity.insert(match downcast::<VisibilityData>(component)? {
+            VisibilityData::Inherited => Visibility::Inherited,
+            VisibilityData::Hidden => Visibility::Hidden,
+            VisibilityData::Visible => Visibility::Visible,
+        });
+        Ok(())
+    },
+};
+
+#[linkme::distributed_slice(crate::COMPONENT_REGISTRY)]
+static CHILD_OF_REGISTRATION: ComponentRegistration = ComponentRegistration {
+    id: "bevy::ChildOf",
+    schema_hash: 0,
+    serialize_fn: |component, format| {
+        let child_of = unsafe { component.deref::<ChildOf>() };
+        encode(&child_of.parent().to_bits(), format)
+    },
+    deserialize_fn: decode::<u64>,
+    get_type_id: TypeId::of::<ChildOf>,
+    type_name: std::any::type_name::<ChildOf>,
+    reg_fn: |registry: &mut TypeRegistry| registry.register::<ChildOf>(),
+    insert_fn: |entity: &mut EntityWorldMut, component: Box<dyn Any>| {
+        let bits = downcast::<u64>(component)?;
+        // The parent must exist, Bevy cannot relate an entity to a missing one
+        let Some(parent) = Entity::try_from_bits(bits)
+            .ok()
+            .filter(|&parent| entity.world().get_entity(parent).is_ok())
+        else {
+            error!("Parent entity {} of {:?} does not exist", bits, entity.id());
+            return Err(HostError::NoEntity);
+        };
+        entity.insert(ChildOf(parent));
+        Ok(())
+    },
+};
+
+#[linkme::distributed_slice(crate::COMPONENT_REGISTRY)]
+static CHILDREN_REGISTRATION: ComponentRegistration = ComponentRegistration {
+    id: "bevy::Children",
+    schema_hash: 0,
+    serialize_fn: |component, format| {
+        let children = unsafe { component.deref::<Children>() };
+        let bits: Vec<u64> = children.iter().map(|child| child.to_bits()).collect();
+        encode(&bits, format)
+    },
+    deserialize_fn: decode::<Vec<u64>>,
+    get_type_id: TypeId::of::<Children>,
+    type_name: std::any::type_name::<Children>,
+    reg_fn: |registry: &mut TypeRegistry| registry.register::<Children>(),
+    insert_fn: |_, _| Err(HostError::ReadOnly),
+};
+
+/// Mod data of the `Time` resource
+#[derive(Serialize, Deserialize)]
+struct TimeData {
+    delta_secs: f32,
+    elapsed_secs: f32,
+    elapsed_secs_f64: f64,
+}
+
+#[linkme::distributed_slice(crate::RESOURCE_REGISTRY)]
+static TIME_REGISTRATION: ResourceRegistration = ResourceRegistration {
+    id: "bevy::Time",
+    schema_hash: 0,
+    serialize_fn: |resource, format| {
+        let time = unsafe { resource.deref::<Time>() };
+        encode(&TimeData {
+            delta_secs: time.delta_secs(),
+            elapsed_secs: time.elapsed_secs(),
+            elapsed_secs_f64: time.elapsed_secs_f64(),
+        }, format)
+    },
+    deserialize_fn: decode::<TimeData>,
+    get_type_id: TypeId::of::<Time>,
+    type_name: std::any::type_name::<Time>,
+    reg_fn: |registry: &mut TypeRegistry| registry.register::<Time>(),
+    insert_fn: |_, _| Err(HostError::ReadOnly),
+};
+
+/// Mod data of a `Sprite`
+#[derive(Serialize, Deserialize)]
+struct SpriteData {
+    image: String,
+    color: [f32; 4],
+    flip_x: bool,
+    flip_y: bool,
+    custom_size: Option<[f32; 2]>,
+}
+
+#[linkme::distributed_slice(crate::COMPONENT_REGISTRY)]
+static SPRITE_REGISTRATION: ComponentRegistration = ComponentRegistration {
+    id: "bevy::Sprite",
+    schema_hash: 0,
+    serialize_fn: |component, format| {
+        let sprite = unsafe { component.deref::<Sprite>() };
+        let color = sprite.color.to_srgba();
+        encode(&SpriteData {
+            image: asset_handle_id(&sprite.image),
+            color: [color.red, color.green, color.blue, color.alpha],
+            flip_x: sprite.flip_x,
+            flip_y: sprite.flip_y,
+            custom_size: sprite.custom_size.map(|size| size.to_array()),
+        }, format)
+    },
+    deserialize_fn: decode::<SpriteData>,
+    get_type_id: TypeId::of::<Sprite>,
+    type_name: std::any::type_name::<Sprite>,
+    reg_fn: |registry: &mut TypeRegistry| registry.register::<Sprite>(),
+    insert_fn: |entity: &mut EntityWorldMut, component: Box<dyn Any>| {
+        let data = downcast::<SpriteData>(component)?;
+        let image = resolve_asset_handle::<Image>(entity.world(), &data.image);
+        let [red, green, blue, alpha] = data.color;
+
+        // Keep the fields mods do not see, like the texture atlas
+        let mut sprite = entity.take::<Sprite>().unwrap_or_default();
+        sprite.image = image;
+        sprite.color = Color::srgba(red, green, blue, alpha);
+        sprite.flip_x = data.flip_x;
+        sprite.flip_y = data.flip_y;
+        sprite.custom_size = data.custom_size.map(Vec2::from_array);
+        entity.insert(sprite);
+        Ok(())
+    },
+};
+
+#[cfg(feature = "audio")]
+#[linkme::distributed_slice(crate::COMPONENT_REGISTRY)]
+static AUDIO_PLAYER_REGISTRATION: ComponentRegistration = ComponentRegistration {
+    id: "bevy::AudioPlayer",
+    schema_hash: 0,
+    serialize_fn: |component, format| {
+        let player = unsafe { component.deref::<AudioPlayer>() };
+        encode(&asset_handle_id(&player.0), format)
+    },
+    deserialize_fn: decode::<String>,
+    get_type_id: TypeId::of::<AudioPlayer>,
+    type_name: std::any::type_name::<AudioPlayer>,
+    reg_fn: |registry: &mut TypeRegistry| registry.register::<AudioPlayer>(),
+    insert_fn: |entity: &mut EntityWorldMut, component: Box<dyn Any>| {
+        let id = downcast::<String>(component)?;
+        let audio = resolve_asset_handle::<AudioSource>(entity.world(), &id);
+        entity.insert(AudioPlayer::new(audio));
+        Ok(())
+    },
+};
+
+#[linkme::distributed_slice(crate::COMPONENT_REGISTRY)]
+static SCENE_ROOT_REGISTRATION: ComponentRegistration = ComponentRegistration {
+    id: "bevy::SceneRoot",
+    schema_hash: 0,
+    serialize_fn: |component, format| {
+        let root = unsafe { component.deref::<SceneRoot>() };
+        encode(&asset_handle_id(&root.0), format)
+    },
+    deserialize_fn: decode::<String>,
+    get_type_id: TypeId::of::<SceneRoot>,
+    type_name: std::any::type_name::<SceneRoot>,
+    reg_fn: |registry: &mut TypeRegistry| registry.register::<SceneRoot>(),
+    insert_fn: |entity: &mut EntityWorldMut, component: Box<dyn Any>| {
+        let id = downcast::<String>(component)?;
+        let scene = resolve_asset_handle::<Scene>(entity.world(), &id);
+        entity.insert(SceneRoot(scene));
+        Ok(())
+    },
+};
+
+#[linkme::distributed_slice(crate::COMPONENT_REGISTRY)]
+static DYNAMIC_SCENE_ROOT_REGISTRATION: ComponentRegistration = ComponentRegistration {
+    id: "bevy::DynamicSceneRoot",
+    schema_hash: 0,
+    serialize_fn: |component, format| {
+        let root = unsafe { component.deref::<DynamicSceneRoot>() };
+        encode(&asset_handle_id(&root.0), format)
+    },
+    deserialize_fn: decode::<String>,
+    get_type_id: TypeId::of::<DynamicSceneRoot>,
+    type_name: std::any::type_name::<DynamicSceneRoot>,
+    reg_fn: |registry: &mut TypeRegistry| registry.register::<DynamicSceneRoot>(),
+    insert_fn: |entity: &mut EntityWorldMut, component: Box<dyn Any>| {
+        let id = downcast::<String>(component)?;
+        let scene = resolve_asset_handle::<DynamicScene>(entity.world(), &id);
+        entity.insert(DynamicSceneRoot(scene));
+        Ok(())
+    },
+};
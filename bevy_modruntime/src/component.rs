@@ -2,30 +2,58 @@
 //!
 //! This module provides functionality for registering and managing components
 //! that can be accessed by mods.
+//!
+//! Components without a registration can still be accessed by their type path, if they
+//! are registered in the `AppTypeRegistry` with `ReflectComponent`, `ReflectSerialize`
+//! and `ReflectDeserialize`. They are serialized with Bevy's reflect serializer, which
+//! uses the serde implementation of the type.
+//!
+//! Components defined by mods are found by id too, see [`crate::dynamic`].
 
+use crate::dynamic::{ModData, ModDefinedComponents};
+use bevy::ecs::component::{ComponentId, Components};
+use bevy::ecs::reflect::ReflectComponent;
+use bevy::ecs::world::EntityWorldMut;
 use bevy::prelude::*;
-use bevy::reflect::TypeRegistry;
+use bevy::ptr::{OwningPtr, Ptr};
+use bevy::reflect::serde::{TypedReflectDeserializer, TypedReflectSerializer};
+use bevy::reflect::{
+    ReflectDeserialize, ReflectFromPtr, ReflectSerialize, TypeRegistration, TypeRegistry,
+};
 pub use bevy_modtypes::HostModResult;
+use bevy_modtypes::{Format, FormatError, HostError};
 use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::sync::{LazyLock, Mutex};
 
 // Component registry using linkme
 #[linkme::distributed_slice]
 pub static COMPONENT_REGISTRY: [ComponentRegistration] = [..];
 
+/// Serialization function of a registration, in the format of a mod
+pub type SerializeFn = fn(Ptr<'_>, Format) -> Vec<u8>;
+
+/// Deserialization function of a registration, failing on malformed data
+pub type DeserializeFn = fn(&[u8], Format) -> Result<Box<dyn Any>, FormatError>;
+
 /// Component registration information
 pub struct ComponentRegistration {
     /// The ID of the component
     pub id: &'static str,
+    /// Hash of the shape of the type, checked against mods, `0` to skip the check
+    pub schema_hash: u64,
     /// Serialization function
-    pub serialize_fn: fn(bevy::ptr::Ptr<'_>) -> Vec<u8>,
-    /// Deserialization function
-    pub deserialize_fn: fn(&[u8]) -> Box<dyn Any>,
+    pub serialize_fn: SerializeFn,
+    /// Deserialization function, failing on malformed data
+    pub deserialize_fn: DeserializeFn,
     /// Type id
     pub get_type_id: fn() -> TypeId,
+    /// Rust type name, for messages
+    pub type_name: fn() -> &'static str,
     /// Reg type function
     pub reg_fn: fn(&mut TypeRegistry),
-    /// Insert component function
-    pub insert_fn: fn(&mut EntityWorldMut, Box<dyn Any>),
+    /// Insert component function, failing for read-only components
+    pub insert_fn: fn(&mut EntityWorldMut, Box<dyn Any>) -> Result<(), HostError>,
 }
 
 /// Find a component registration by ID
@@ -33,4 +61,301 @@ pub fn find_component_registration(id: &str) -> Option<&'static ComponentRegistr
     COMPONENT_REGISTRY.iter().find(|reg| reg.id == id)
 }
 
+/// An id registered by two types
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateId {
+    /// The registered id
+    pub id: &'static str,
+    /// Type name of the registration found by the id
+    pub first: &'static str,
+    /// Type name of the registration shadowed by the first one
+    pub second: &'static str,
+}
+
+/// Find the component ids registered by more than one type
+///
+/// Lookups return the first registration of an id, the others are never reached.
+pub fn duplicate_component_ids() -> Vec<DuplicateId> {
+    find_duplicate_ids(
+        COMPONENT_REGISTRY
+            .iter()
+            .map(|registration| (registration.id, (registration.type_name)())),
+    )
+}
+
+/// Find the duplicates among `(id, type name)` pairs, in registration order
+pub(crate) fn find_duplicate_ids(
+    registrations: impl Iterator<Item = (&'static str, &'static str)>,
+) -> Vec<DuplicateId> {
+    let mut first_types = HashMap::new();
+    let mut duplicates = Vec::new();
+    for (id, type_name) in registrations {
+        match first_types.get(id) {
+            Some(&first) => duplicates.push(DuplicateId {
+                id,
+                first,
+                second: type_name,
+            }),
+            None => {
+                first_types.insert(id, type_name);
+            }
+        }
+    }
+    duplicates
+}
+
+/// Ids of the reflected components already reported as missing reflected traits
+static UNREFLECTABLE_WARNED: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Default::default);
+
+/// A component accessible to mods, by registration, by reflection or defined by a mod
+pub enum ModComponent<'a> {
+    /// A component registered with `#[mod_component]`
+    Registered(&'static ComponentRegistration),
+    /// A component defined by a mod, holding the data serialized by the mods
+    Defined {
+        /// The component id
+        id: &'a str,
+        /// Id of the Bevy component holding the data
+        component_id: ComponentId,
+    },
+    /// A reflected component, found by type path
+    Reflected {
+        /// Type registration of the component
+        registration: &'a TypeRegistration,
+        /// The type registry, to serialize nested types
+        registry: &'a TypeRegistry,
+    },
+}
+
+impl<'a> ModComponent<'a> {
+    /// Find a component by id
+    ///
+    /// Registered components come first, then the components defined by mods, then the
+    /// reflected components by full type path, then by short type path like `Transform`.
+    pub fn find(
+        id: &'a str,
+        registry: &'a TypeRegistry,
+        defined: Option<&ModDefinedComponents>,
+    ) -> Option<Self> {
+        if let Some(registration) = find_component_registration(id) {
+            return Some(ModComponent::Registered(registration));
+        }
+        if let Some(component) = defined.and_then(|defined| defined.get(id)) {
+            return Some(ModComponent::Defined {
+                id,
+                component_id: component.component_id,
+            });
+        }
 
+        let registration = registry
+            .get_with_type_path(id)
+            .or_else(|| registry.get_with_short_type_path(id))?;
+        let reflectable = registration.contains::<ReflectComponent>()
+            && registration.contains::<ReflectFromPtr>()
+            && registration.contains::<ReflectSerialize>()
+            && registration.contains::<ReflectDeserialize>();
+        if !reflectable {
+            // Found on every access of the mods, warn once per id
+            if UNREFLECTABLE_WARNED.lock().unwrap().insert(id.to_string()) {
+                warn!(
+                    "Component '{}' needs to reflect Component, Serialize and Deserialize to be used by mods",
+                    id
+                );
+            }
+            return None;
+        }
+
+        Some(ModComponent::Reflected {
+            registration,
+            registry,
+        })
+    }
+
+    /// Get the id of the component in the world, if it is registered there
+    pub fn component_id(&self, components: &Components) -> Option<ComponentId> {
+        match self {
+            ModComponent::Registered(registration) => {
+                components.get_id((registration.get_type_id)())
+            }
+            ModComponent::Defined { component_id, .. } => Some(*component_id),
+            ModComponent::Reflected { registration, .. } => {
+                components.get_id(TypeRegistration::type_id(registration))
+            }
+        }
+    }
+
+    /// Serialize the component behind a pointer
+    ///
+    /// # Safety
+    /// The pointer must point to a value of the component type.
+    pub unsafe fn serialize(&self, component: Ptr<'_>, format: Format) -> Vec<u8> {
+        match self {
+            ModComponent::Registered(registration) => {
+                (registration.serialize_fn)(component, format)
+            }
+            ModComponent::Defined { id, .. } => {
+                let component = unsafe { component.deref::<ModData>() };
+                if component.format != format {
+                    error!(
+                        "Component '{}' is serialized in {} by its mods, it cannot be read in {}",
+                        id, component.format, format
+                    );
+                    return Vec::new();
+                }
+                component.data.clone()
+            }
+            ModComponent::Reflected {
+                registration,
+                registry,
+            } => {
+                let from_ptr = registration.data::<ReflectFromPtr>().unwrap();
+                let value = unsafe { from_ptr.as_reflect(component) };
+                let serializer = TypedReflectSerializer::new(value.as_partial_reflect(), registry);
+                match format.encode(&serializer) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        error!(
+                            "Failed to serialize component {}: {}",
+                            registration.type_info().type_path(),
+                            e
+                        );
+                        Vec::new()
+                    }
+                }
+            }
+        }
+    }
+
+    /// Deserialize the component and insert it into an entity
+    ///
+    /// Malformed data is logged and leaves the entity unchanged.
+    pub fn insert(
+        &self,
+        entity: &mut EntityWorldMut,
+        data: &[u8],
+        format: Format,
+    ) -> Result<(), HostError> {
+        self.decode(data, format)?.insert(entity)
+    }
+
+    /// Deserialize the component, to insert it later
+    ///
+    /// Malformed data is logged.
+    pub fn decode(&self, data: &[u8], format: Format) -> Result<DecodedComponent<'a>, HostError> {
+        match *self {
+            ModComponent::Registered(registration) => {
+                let component = (registration.deserialize_fn)(data, format).map_err(|e| {
+                    error!("Failed to deserialize component {}: {}", registration.id, e);
+                    HostError::InvalidData
+                })?;
+                Ok(DecodedComponent::Registered(registration, component))
+            }
+            ModComponent::Defined { component_id, .. } => Ok(DecodedComponent::Defined(
+                component_id,
+                ModData {
+                    format,
+                    data: data.to_vec(),
+                },
+            )),
+            ModComponent::Reflected {
+                registration,
+                registry,
+            } => {
+                let deserializer = TypedReflectDeserializer::new(registration, registry);
+                match format.decode_seed(deserializer, data) {
+                    Ok(value) => Ok(DecodedComponent::Reflected {
+                        value,
+                        registration,
+                        registry,
+                    }),
+                    Err(e) => {
+                        error!(
+                            "Failed to deserialize component {}: {}",
+                            registration.type_info().type_path(),
+                            e
+                        );
+                        Err(HostError::InvalidData)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A component deserialized from the data of a mod, ready to be inserted
+pub enum DecodedComponent<'a> {
+    /// A registered component, as returned by its deserialization function
+    Registered(&'static ComponentRegistration, Box<dyn Any>),
+    /// A component defined by a mod, with the id of the Bevy component holding it
+    Defined(ComponentId, ModData),
+    /// A reflected component
+    Reflected {
+        /// The deserialized value
+        value: Box<dyn PartialReflect>,
+        /// Type registration of the component
+        registration: &'a TypeRegistration,
+        /// The type registry, to insert nested types
+        registry: &'a TypeRegistry,
+    },
+}
+
+impl DecodedComponent<'_> {
+    /// Insert the component into an entity
+    ///
+    /// Fails for read-only components, and components referring to missing entities.
+    pub fn insert(self, entity: &mut EntityWorldMut) -> Result<(), HostError> {
+        match self {
+            DecodedComponent::Registered(registration, component) => {
+                (registration.insert_fn)(entity, component)?;
+            }
+            DecodedComponent::Defined(component_id, component) => {
+                // SAFETY: the component was registered with the layout of `ModData`
+                OwningPtr::make(component, |ptr| unsafe {
+                    entity.insert_by_id(component_id, ptr);
+                });
+            }
+            DecodedComponent::Reflected {
+                value,
+                registration,
+                registry,
+            } => {
+                let reflect_component = registration.data::<ReflectComponent>().unwrap();
+                reflect_component.insert(entity, value.as_ref(), registry);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_duplicate_ids_reports_shadowed_types() {
+        let registrations = [
+            ("square", "game::Square"),
+            ("circle", "game::Circle"),
+            ("square", "other::Square"),
+            ("square", "third::Square"),
+        ];
+        let duplicates = find_duplicate_ids(registrations.into_iter());
+        let found: Vec<_> = duplicates
+            .iter()
+            .map(|duplicate| (duplicate.id, duplicate.first, duplicate.second))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("square", "game::Square", "other::Square"),
+                ("square", "game::Square", "third::Square"),
+            ]
+        );
+    }
+
+    #[test]
+    fn find_duplicate_ids_accepts_unique_ids() {
+        let registrations = [("square", "game::Square"), ("circle", "game::Circle")];
+        assert!(find_duplicate_ids(registrations.into_iter()).is_empty());
+    }
+}
@@ -0,0 +1,62 @@
+//! Diagnostics for mods
+//!
+//! Each mod system publishes its wall time under `mod/<mod name>/<system name>`,
+//! and each mod publishes the total wall time of its systems under `mod/<mod name>`.
+//! When fuel metering is enabled, the fuel consumed by each system is published
+//! under `mod/<mod name>/<system name>/fuel`.
+//!
+//! Names are sent by mods, a `/` in them is replaced by `_` and an empty name becomes
+//! `unknown`.
+
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, DiagnosticsStore};
+
+/// Make a name a valid component of a diagnostic path, which cannot be empty or hold `/`
+fn path_component(name: &str) -> String {
+    if name.is_empty() {
+        return "unknown".to_string();
+    }
+    name.replace('/', "_")
+}
+
+/// Diagnostic path of the total wall time of a mod
+pub fn mod_diagnostic_path(mod_name: &str) -> DiagnosticPath {
+    DiagnosticPath::from_components(["mod", &path_component(mod_name)])
+}
+
+/// Diagnostic path of the wall time of a mod system
+pub fn system_diagnostic_path(mod_name: &str, system_name: &str) -> DiagnosticPath {
+    DiagnosticPath::from_components([
+        "mod",
+        &path_component(mod_name),
+        &path_component(system_name),
+    ])
+}
+
+/// Diagnostic path of the fuel consumed by a mod system
+pub fn system_fuel_diagnostic_path(mod_name: &str, system_name: &str) -> DiagnosticPath {
+    DiagnosticPath::from_components([
+        "mod",
+        &path_component(mod_name),
+        &path_component(system_name),
+        "fuel",
+    ])
+}
+
+/// Register the diagnostics of a mod and its systems
+pub(crate) fn register_mod_diagnostics(
+    store: &mut DiagnosticsStore,
+    mod_name: &str,
+    system_names: &[String],
+    fuel_metering: bool,
+) {
+    store.add(Diagnostic::new(mod_diagnostic_path(mod_name)).with_suffix("ms"));
+    for system_name in system_names {
+        store.add(Diagnostic::new(system_diagnostic_path(mod_name, system_name)).with_suffix("ms"));
+        if fuel_metering {
+            store.add(Diagnostic::new(system_fuel_diagnostic_path(
+                mod_name,
+                system_name,
+            )));
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Mod-defined components and resources
+//!
+//! Mods can define components and resources the host does not know about, listed by their
+//! `component_def!` and `resource_def!`. Each one becomes a dynamic Bevy component or
+//! resource holding the bytes serialized by the mods, so mods can attach it to entities,
+//! query it and depend on it by id. The host never decodes the data, mods sharing a
+//! component or resource must use the same format.
+//!
+//! Bevy cannot remove components, a mod-defined component or resource stays registered
+//! once its mod is unloaded, and is reused when the mod is reloaded. Resources keep their
+//! value, so the state of a mod survives reloading it.
+
+use crate::component::find_component_registration;
+use crate::resource::find_resource_registration;
+use bevy::ecs::change_detection::MaybeLocation;
+use bevy::ecs::component::{ComponentCloneBehavior, ComponentDescriptor, ComponentId, StorageType};
+use bevy::ecs::world::unsafe_world_cell::UnsafeWorldCell;
+use bevy::prelude::*;
+use bevy::ptr::OwningPtr;
+use bevy_modtypes::{Format, HostError, ModComponentDef, ModResourceDef, SchemaKind};
+use std::alloc::Layout;
+use std::collections::HashMap;
+
+/// Data of a mod-defined component or resource, as serialized by a mod
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModData {
+    /// Format the data is serialized in
+    pub format: Format,
+    /// The serialized data
+    pub data: Vec<u8>,
+}
+
+/// Drop the data of a mod-defined component or resource
+///
+/// # Safety
+/// The pointer must point to a `ModData`.
+unsafe fn drop_mod_data(ptr: OwningPtr<'_>) {
+    unsafe { ptr.drop_as::<ModData>() }
+}
+
+/// Describe a component or resource holding `ModData`
+fn mod_data_descriptor(id: &str) -> ComponentDescriptor {
+    // SAFETY: the drop function matches the layout, and the data is Send + Sync
+    unsafe {
+        ComponentDescriptor::new_with_layout(
+            id.to_string(),
+            StorageType::Table,
+            Layout::new::<ModData>(),
+            Some(drop_mod_data),
+            true,
+            ComponentCloneBehavior::Ignore,
+        )
+    }
+}
+
+/// A component or resource defined by a mod
+#[derive(Debug, Clone)]
+pub struct ModDefinition {
+    /// Id of the Bevy component or resource holding the data
+    pub component_id: ComponentId,
+    /// Name of the mod defining the component or resource
+    pub mod_name: String,
+    /// Hash of the shape of the type in the defining mod, `0` if not checked
+    pub schema_hash: u64,
+}
+
+/// Components defined by the loaded mods, by id
+#[derive(Resource, Debug, Default)]
+pub struct ModDefinedComponents(HashMap<String, ModDefinition>);
+
+impl ModDefinedComponents {
+    /// Get a mod-defined component by id
+    pub fn get(&self, id: &str) -> Option<&ModDefinition> {
+        self.0.get(id)
+    }
+
+    /// Iterate over the mod-defined components and their ids
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ModDefinition)> {
+        self.0.iter().map(|(id, component)| (id.as_str(), component))
+    }
+}
+
+/// Resources defined by the loaded mods, by id
+#[derive(Resource, Debug, Default)]
+pub struct ModDefinedResources(HashMap<String, ModDefinition>);
+
+impl ModDefinedResources {
+    /// Get a mod-defined resource by id
+    pub fn get(&self, id: &str) -> Option<&ModDefinition> {
+        self.0.get(id)
+    }
+
+    /// Iterate over the mod-defined resources and their ids
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ModDefinition)> {
+        self.0.iter().map(|(id, resource)| (id.as_str(), resource))
+    }
+}
+
+/// Check a definition against the ones of the other mods
+///
+/// Returns whether the id is new. A reloaded mod keeps its definition, with the schema of
+/// its new version.
+fn check_definition(
+    defined: &mut HashMap<String, ModDefinition>,
+    kind: SchemaKind,
+    mod_name: &str,
+    id: &str,
+    schema_hash: u64,
+) -> bool {
+    let Some(defined) = defined.get_mut(id) else {
+        return true;
+    };
+    if defined.mod_name != mod_name {
+        warn!(
+            "Mod '{}' defines {} '{}', already defined by mod '{}'",
+            mod_name, kind, id, defined.mod_name
+        );
+    } else if defined.schema_hash != schema_hash {
+        warn!(
+            "Schema of {} '{}' changed in mod '{}', its existing data may not decode",
+            kind, id, mod_name
+        );
+        defined.schema_hash = schema_hash;
+    }
+    false
+}
+
+/// Register the components defined by a mod
+///
+/// Ids registered by the host or defined by another mod are skipped.
+pub(crate) fn register_mod_components(
+    world: &mut World,
+    mod_name: &str,
+    definitions: Vec<ModComponentDef>,
+) {
+    for ModComponentDef { id, schema_hash } in definitions {
+        if let Some(registration) = find_component_registration(&id) {
+            error!(
+                "Mod '{}' defines component '{}', already registered by the host for {}",
+                mod_name,
+                id,
+                (registration.type_name)()
+            );
+            continue;
+        }
+
+        let mut defined = world.resource_mut::<ModDefinedComponents>();
+        if !check_definition(&mut defined.0, SchemaKind::Component, mod_name, &id, schema_hash) {
+            continue;
+        }
+
+        let component_id = world.register_component_with_descriptor(mod_data_descriptor(&id));
+        info!("Mod '{}' defines component '{}'", mod_name, id);
+        world.resource_mut::<ModDefinedComponents>().0.insert(
+            id,
+            ModDefinition {
+                component_id,
+                mod_name: mod_name.to_string(),
+                schema_hash,
+            },
+        );
+    }
+}
+
+/// Register the resources defined by a mod, with their initial value
+///
+/// Ids registered by the host or defined by another mod are skipped. Resources defined
+/// before keep their value.
+pub(crate) fn register_mod_resources(
+    world: &mut World,
+    mod_name: &str,
+    definitions: Vec<ModResourceDef>,
+    format: Format,
+) {
+    for ModResourceDef {
+        id,
+        schema_hash,
+        initial,
+    } in definitions
+    {
+        if let Some(registration) = find_resource_registration(&id) {
+            error!(
+                "Mod '{}' defines resource '{}', already registered by the host for {}",
+                mod_name,
+                id,
+                (registration.type_name)()
+            );
+            continue;
+        }
+
+        let mut defined = world.resource_mut::<ModDefinedResources>();
+        if !check_definition(&mut defined.0, SchemaKind::Resource, mod_name, &id, schema_hash) {
+            continue;
+        }
+
+        let component_id = world.register_resource_with_descriptor(mod_data_descriptor(&id));
+        info!("Mod '{}' defines resource '{}'", mod_name, id);
+        world.resource_mut::<ModDefinedResources>().0.insert(
+            id,
+            ModDefinition {
+                component_id,
+                mod_name: mod_name.to_string(),
+                schema_hash,
+            },
+        );
+        insert_mod_data(world, component_id, initial, format);
+    }
+}
+
+/// Insert the value of a mod-defined resource
+fn insert_mod_data(world: &mut World, component_id: ComponentId, data: Vec<u8>, format: Format) {
+    // SAFETY: the resource was registered with the layout of `ModData`
+    OwningPtr::make(ModData { format, data }, |ptr| unsafe {
+        world.insert_resource_by_id(component_id, ptr, MaybeLocation::caller());
+    });
+}
+
+/// Query a mod-defined resource from the Bevy world, in the format of a mod
+pub(crate) fn query_defined_resource(
+    world: &UnsafeWorldCell<'_>,
+    resource_id: &str,
+    format: Format,
+) -> Option<Vec<u8>> {
+    let world = unsafe { world.world() };
+    let component_id = world
+        .get_resource::<ModDefinedResources>()?
+        .get(resource_id)?
+        .component_id;
+    let resource = unsafe { world.get_resource_by_id(component_id)?.deref::<ModData>() };
+    if resource.format != format {
+        error!(
+            "Resource '{}' is serialized in {} by its mods, it cannot be read in {}",
+            resource_id, resource.format, format
+        );
+        return None;
+    }
+    Some(resource.data.clone())
+}
+
+/// Write a mod-defined resource into the Bevy world
+pub(crate) fn insert_defined_resource(
+    world: &mut World,
+    resource_id: &str,
+    data: Vec<u8>,
+    format: Format,
+) -> Result<(), HostError> {
+    let Some(component_id) = world
+        .get_resource::<ModDefinedResources>()
+        .and_then(|defined| defined.get(resource_id))
+        .map(|resource| resource.component_id)
+    else {
+        error!("Resource registration not found for ID: {}", resource_id);
+        return Err(HostError::UnknownId);
+    };
+    insert_mod_data(world, component_id, data, format);
+    Ok(())
+}
@@ -0,0 +1,244 @@
+//! Shared wasm engine
+//!
+//! All mods are compiled and run by one [`ModEngine`], configured through [`ModEngineConfig`].
+//! Compiled modules are kept in memory, and optionally serialized to a cache directory
+//! keyed by a SHA-256 of the wasm bytes and the engine settings, so later starts skip
+//! compilation.
+
+use bevy::prelude::*;
+use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use wasmtime::{Config, Engine, Module};
+
+// Re-export the optimization level used in the config
+pub use wasmtime::OptLevel;
+
+/// Number of cache writes, naming their temporary files
+static CACHE_WRITE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Configuration of the shared wasm engine
+#[derive(Debug, Clone)]
+pub struct ModEngineConfig {
+    /// Cranelift optimization level
+    pub opt_level: OptLevel,
+    /// Whether functions are compiled in parallel
+    pub parallel_compilation: bool,
+    /// Extra cranelift flags as `(name, value)`
+    pub cranelift_flags: Vec<(String, String)>,
+    /// Directory of the compiled module cache, `None` to disable it
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl Default for ModEngineConfig {
+    fn default() -> Self {
+        Self {
+            opt_level: OptLevel::Speed,
+            parallel_compilation: true,
+            cranelift_flags: Vec::new(),
+            cache_dir: None,
+        }
+    }
+}
+
+impl ModEngineConfig {
+    /// Set the cranelift optimization level
+    pub fn with_opt_level(mut self, opt_level: OptLevel) -> Self {
+        self.opt_level = opt_level;
+        self
+    }
+
+    /// Enable or disable parallel compilation
+    pub fn with_parallel_compilation(mut self, enabled: bool) -> Self {
+        self.parallel_compilation = enabled;
+        self
+    }
+
+    /// Add a cranelift flag
+    ///
+    /// Flags are passed to cranelift as is, see `wasmtime::Config::cranelift_flag_set`.
+    pub fn with_cranelift_flag(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.cranelift_flags.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set the directory of the compiled module cache
+    pub fn with_cache_dir(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(cache_dir.into());
+        self
+    }
+
+    /// Build the wasmtime config
+    pub fn to_wasmtime_config(&self, fuel_metering: bool) -> Config {
+        let mut config = Config::new();
+        config
+            .cranelift_opt_level(self.opt_level)
+            .parallel_compilation(self.parallel_compilation)
+            .consume_fuel(fuel_metering);
+        for (name, value) in &self.cranelift_flags {
+            // Safety: the flags are provided by the host developer, who is responsible for them
+            unsafe {
+                config.cranelift_flag_set(name, value);
+            }
+        }
+        config
+    }
+}
+
+/// Resource holding the wasm engine shared by all mods
+#[derive(Resource, Clone)]
+pub struct ModEngine {
+    /// The wasm engine
+    engine: Engine,
+    /// Directory of the compiled module cache
+    cache_dir: Option<PathBuf>,
+    /// Compiled modules by key
+    modules: Arc<Mutex<HashMap<ModuleKey, Module>>>,
+}
+
+impl ModEngine {
+    /// Create the engine from its configuration
+    pub fn new(config: &ModEngineConfig, fuel_metering: bool) -> anyhow::Result<Self> {
+        let engine = Engine::new(&config.to_wasmtime_config(fuel_metering))?;
+        Ok(Self {
+            engine,
+            cache_dir: config.cache_dir.clone(),
+            modules: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// Get the wasm engine
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// Load a module from a wasm file
+    pub fn load_module(&self, path: impl AsRef<Path>) -> anyhow::Result<Module> {
+        let bytes = std::fs::read(path)?;
+        self.compile_module(&bytes)
+    }
+
+    /// Compile a module, using the in-memory and on-disk caches when possible
+    pub fn compile_module(&self, bytes: &[u8]) -> anyhow::Result<Module> {
+        let key = self.module_key(bytes);
+
+        if let Some(module) = self.modules.lock().unwrap().get(&key) {
+            return Ok(module.clone());
+        }
+
+        let module = match self.cache_path(key) {
+            Some(cache_path) => self.compile_module_cached(bytes, &cache_path)?,
+            None => Module::new(&self.engine, bytes)?,
+        };
+
+        self.modules.lock().unwrap().insert(key, module.clone());
+        Ok(module)
+    }
+
+    /// Compile a module through the on-disk cache
+    fn compile_module_cached(&self, bytes: &[u8], cache_path: &Path) -> anyhow::Result<Module> {
+        if cache_path.exists() {
+            // Safety: the cache only holds modules serialized by this engine, and the key
+            // includes the engine compatibility hash
+            match unsafe { Module::deserialize_file(&self.engine, cache_path) } {
+                Ok(module) => {
+                    debug!("Loaded compiled module from cache '{}'", cache_path.display());
+                    return Ok(module);
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to load compiled module from cache '{}': {}",
+                        cache_path.display(),
+                        e
+                    );
+                    // Drop the broken entry, it is written again below
+                    if let Err(e) = std::fs::remove_file(cache_path) {
+                        warn!(
+                            "Failed to remove compiled module from cache '{}': {}",
+                            cache_path.display(),
+                            e
+                        );
+                    }
+                }
+            }
+        }
+
+        let module = Module::new(&self.engine, bytes)?;
+
+        // A failing cache write only costs the next start a compilation. The module is
+        // written to a temporary file first and renamed into place, so a crash or another
+        // host writing the same entry never leaves a truncated module in the cache.
+        let write_cache = || -> anyhow::Result<()> {
+            if let Some(parent) = cache_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let temp_path = cache_path.with_extension(format!(
+                "{}.{}.tmp",
+                std::process::id(),
+                CACHE_WRITE_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            let result = std::fs::write(&temp_path, module.serialize()?)
+                .and_then(|()| std::fs::rename(&temp_path, cache_path));
+            if result.is_err() {
+                let _ = std::fs::remove_file(&temp_path);
+            }
+            Ok(result?)
+        };
+        if let Err(e) = write_cache() {
+            warn!(
+                "Failed to write compiled module to cache '{}': {}",
+                cache_path.display(),
+                e
+            );
+        }
+
+        Ok(module)
+    }
+
+    /// Key of a module, from its wasm bytes and the engine compatibility
+    ///
+    /// Cached modules are loaded without validation, so the key is a SHA-256 rather than a
+    /// fast hash. The compatibility hash covers the wasmtime version and the settings
+    /// affecting the compiled code.
+    fn module_key(&self, bytes: &[u8]) -> ModuleKey {
+        let mut hasher = Sha256Hasher(Sha256::new());
+        self.engine.precompile_compatibility_hash().hash(&mut hasher);
+        let mut digest = hasher.0;
+        digest.update(bytes);
+        ModuleKey(digest.finalize().into())
+    }
+
+    /// Path of a module in the on-disk cache
+    fn cache_path(&self, key: ModuleKey) -> Option<PathBuf> {
+        self.cache_dir
+            .as_ref()
+            .map(|cache_dir| cache_dir.join(format!("{}.cwasm", key)))
+    }
+}
+
+/// Key of a compiled module in the caches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ModuleKey([u8; 32]);
+
+impl std::fmt::Display for ModuleKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+/// Feed a `Hash` value to a SHA-256
+struct Sha256Hasher(Sha256);
+
+impl Hasher for Sha256Hasher {
+    fn finish(&self) -> u64 {
+        // Only the digest is used
+        0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+}
@@ -4,44 +4,112 @@
 //! It handles WebAssembly sandboxing and communication between mods and the host application.
 
 pub mod asset;
+pub mod builtin;
 pub mod component;
+pub mod diagnostic;
+pub mod dynamic;
+pub mod engine;
+pub mod loading;
 pub mod log;
+pub mod package;
+pub mod param;
+pub mod permission;
 pub mod query;
 pub mod resource;
+pub mod schema;
+pub mod sdk;
 pub mod spawn;
+pub mod storage;
 pub mod system;
 mod utils;
+pub mod wasm_mod;
 
+use bevy::asset::io::AssetSourceBuilder;
+use bevy::diagnostic::DiagnosticsStore;
 use bevy::ecs::world::unsafe_world_cell::UnsafeWorldCell;
 use bevy::prelude::*;
 use bevy_modtypes::SystemInfo;
 use std::cell::UnsafeCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::RwLock;
-use utils::*;
-use wasmtime::{Engine, Instance, Linker, Module, Store};
-use wasmtime_wasi::WasiCtxBuilder;
+use std::time::Duration;
+use wasmtime::{Instance, Store};
 use wasmtime_wasi::preview1::WasiP1Ctx;
 
 // Re-export asset handle
-pub use asset::{AssetInfo, host_handle_define_asset};
+pub use asset::{
+    AssetInfo, BytesAsset, BytesAssetLoader, ModAssetHandles, ModAssetType, TextAsset,
+    TextAssetLoader, host_handle_asset_load_state, host_handle_define_asset,
+    host_handle_load_asset, host_handle_read_asset, mod_asset_id, mod_asset_path, new_builtin_asset,
+};
+
+// Re-export engine
+pub use engine::{ModEngine, ModEngineConfig, OptLevel};
+
+// Re-export loading state
+pub use loading::{ModLoadEntry, ModLoadStatus, ModLoadingEvent, ModLoadingState, UnloadMod};
 
 // Re-export log handle
-pub use log::host_handle_log;
+pub use bevy_modtypes::LogLevel;
+pub use log::{
+    LogRateLimiter, ModLogBuffer, ModLogEntry, emit_log_record, host_handle_log,
+};
 
 // Re-export component registry and registration
-pub use component::{COMPONENT_REGISTRY, ComponentRegistration, HostModResult};
+pub use component::{
+    COMPONENT_REGISTRY, ComponentRegistration, DeserializeFn, DuplicateId, HostModResult,
+    SerializeFn, duplicate_component_ids,
+};
+
+// Re-export mod-defined components and resources
+pub use bevy_modtypes::{ModComponentDef, ModResourceDef};
+pub use dynamic::{ModData, ModDefinedComponents, ModDefinedResources, ModDefinition};
+
+// Re-export mod storage
+pub use storage::{
+    ModStorage, host_handle_storage_get, host_handle_storage_remove, host_handle_storage_set,
+};
 
 // Re-export query
 pub use query::host_handle_query_components;
 
+// Re-export system param handles
+pub use param::{host_handle_apply_system_writes, host_handle_fetch_system_params};
+
 // Re-export resource registry and registration
-pub use resource::{RESOURCE_REGISTRY, ResourceRegistration, host_handle_query_resources};
+pub use resource::{
+    RESOURCE_REGISTRY, ResourceRegistration, duplicate_resource_ids, host_handle_query_resources,
+};
 
 // Re-export system handle
-pub use system::{ModSystems, execute_mod_startup_systems, execute_mod_update_systems};
+pub use system::{
+    ModSystemStates, ModSystems, execute_mod_startup_systems, execute_mod_update_systems,
+    host_handle_set_system_enabled,
+};
+
+// Re-export mod package
+pub use package::{MOD_ASSET_SOURCE, ModManifest, ModPackage, ModPackages};
+
+// Re-export mod permissions
+pub use permission::ModPermissions;
+
+// Re-export wasm mod asset
+pub use wasm_mod::{ModAssets, WasmMod, WasmModLoader, WasmModLoaderError};
+
+// Re-export schema checks
+pub use bevy_modtypes::SchemaKind;
+pub use schema::host_handle_check_schema;
+
+// Re-export sdk generator
+pub use sdk::SdkGenerator;
+
+// Re-export serialization formats
+pub use bevy_modtypes::{Format, FormatError};
+
+// Re-export host errors sent to mods
+pub use bevy_modtypes::HostError;
 
 // Re-export spawn functionality
 pub use spawn::host_handle_spawn_entities;
@@ -49,23 +117,65 @@ pub use spawn::host_handle_spawn_entities;
 // Re-export the mod_component macro
 pub use bevy_modruntime_macros::{mod_component, mod_resource};
 
-use crate::system::ModSystemInfo;
-use crate::system::ModSystemSchedule;
+use crate::loading::{ModLoadingTasks, poll_mod_loading, start_mod_loading};
+use crate::wasm_mod::{instantiate_mod_assets, load_mod_assets};
+use crate::param::DeferredWrite;
+use crate::system::SystemStateRequest;
 
 /// Plugin for mod
+///
+/// Must be added before `AssetPlugin`, or `DefaultPlugins` which include it, since it
+/// registers the `mod://` asset source. Adding it later panics.
 #[derive(Debug, Resource, Clone)]
 pub struct WasmModPlugin {
     /// All mod we will load.
     mod_paths: Vec<String>,
-    /// Call while insert new asset, return the asset id
+    /// Mods we will load through the asset server.
+    mod_assets: Vec<String>,
+    /// Directory of the asset folders of mods without a package, in the default asset source.
+    mod_asset_dir: String,
+    /// Call while insert new asset, return the asset id.
+    /// Defaults to the built-in handling of common asset types.
     new_asset_fn: fn(&mut World, AssetInfo) -> String,
+    /// Whether to measure the fuel consumed by mod systems
+    fuel_metering: bool,
+    /// Whether mods with disjoint access run their update systems in parallel
+    parallel_execution: bool,
+    /// Configuration of the shared wasm engine
+    engine_config: ModEngineConfig,
+    /// Permissions of mods without their own permissions
+    default_permissions: ModPermissions,
+    /// Permissions of each mod, by mod name
+    mod_permissions: HashMap<String, ModPermissions>,
+    /// Minimum level of the logs of mods without their own level
+    default_log_level: LogLevel,
+    /// Minimum level of the logs of each mod, by mod name
+    mod_log_levels: HashMap<String, LogLevel>,
+    /// Minimum time between two identical logs of a mod, `None` to emit them all
+    log_repeat_interval: Option<Duration>,
+    /// Number of log records kept per mod in the `ModLogBuffer`
+    log_buffer_capacity: usize,
+    /// Preferred format of the data exchanged with mods
+    format: Format,
 }
 
 impl Default for WasmModPlugin {
     fn default() -> Self {
         Self {
             mod_paths: Vec::new(),
-            new_asset_fn: |_, _| String::from(""),
+            mod_assets: Vec::new(),
+            mod_asset_dir: String::from("mods"),
+            new_asset_fn: new_builtin_asset,
+            fuel_metering: false,
+            parallel_execution: true,
+            engine_config: ModEngineConfig::default(),
+            default_permissions: ModPermissions::default(),
+            mod_permissions: HashMap::new(),
+            default_log_level: LogLevel::Trace,
+            mod_log_levels: HashMap::new(),
+            log_repeat_interval: Some(Duration::from_secs(1)),
+            log_buffer_capacity: 256,
+            format: Format::Bincode,
         }
     }
 }
@@ -83,24 +193,217 @@ impl WasmModPlugin {
         self
     }
 
+    /// Adds a mod to be loaded through the asset server
+    ///
+    /// The path may use any asset source, and the mod is reloaded when the asset changes.
+    pub fn add_mod_asset(mut self, path: impl Into<String>) -> Self {
+        self.mod_assets.push(path.into());
+        self
+    }
+
+    /// Set the directory of the asset folders of mods without a package
+    ///
+    /// A mod loads its assets from `<dir>/<mod name>` in the default asset source.
+    pub fn set_mod_asset_dir(mut self, dir: impl Into<String>) -> Self {
+        self.mod_asset_dir = dir.into();
+        self
+    }
+
     /// Set the new asset fn while plugin onload
     pub fn set_new_asset_fn(mut self, func: fn(&mut World, AssetInfo) -> String) -> Self {
         self.new_asset_fn = func;
         self
     }
+
+    /// Enable or disable fuel metering of mod systems
+    ///
+    /// When enabled, the fuel consumed by each system is published as a diagnostic.
+    pub fn set_fuel_metering(mut self, enabled: bool) -> Self {
+        self.fuel_metering = enabled;
+        self
+    }
+
+    /// Enable or disable parallel execution of mods
+    ///
+    /// When enabled, mods whose update systems declare disjoint access through their
+    /// parameters run in parallel, one system of each mod at a time. World writes made by
+    /// those systems are applied before the next systems of the mods run.
+    pub fn set_parallel_execution(mut self, enabled: bool) -> Self {
+        self.parallel_execution = enabled;
+        self
+    }
+
+    /// Set the configuration of the shared wasm engine
+    pub fn set_engine_config(mut self, config: ModEngineConfig) -> Self {
+        self.engine_config = config;
+        self
+    }
+
+    /// Set the permissions of mods without their own permissions
+    ///
+    /// By default, mods get no permissions.
+    pub fn set_default_permissions(mut self, permissions: ModPermissions) -> Self {
+        self.default_permissions = permissions;
+        self
+    }
+
+    /// Set the permissions of a mod, by mod name
+    pub fn set_mod_permissions(
+        mut self,
+        mod_name: impl Into<String>,
+        permissions: ModPermissions,
+    ) -> Self {
+        self.mod_permissions.insert(mod_name.into(), permissions);
+        self
+    }
+
+    /// Get the permissions of a mod
+    pub fn permissions(&self, mod_name: &str) -> &ModPermissions {
+        self.mod_permissions
+            .get(mod_name)
+            .unwrap_or(&self.default_permissions)
+    }
+
+    /// Set the minimum level of the logs of mods without their own level
+    ///
+    /// Defaults to `Trace`, leaving the filtering to the `LogPlugin`.
+    pub fn set_default_log_level(mut self, level: LogLevel) -> Self {
+        self.default_log_level = level;
+        self
+    }
+
+    /// Set the minimum level of the logs of a mod, by mod name
+    ///
+    /// It takes precedence over the `log_level` of the mod manifest.
+    pub fn set_mod_log_level(mut self, mod_name: impl Into<String>, level: LogLevel) -> Self {
+        self.mod_log_levels.insert(mod_name.into(), level);
+        self
+    }
+
+    /// Set the minimum time between two identical logs of a mod
+    ///
+    /// Repeats within the interval are dropped and counted in the next emitted log.
+    /// Defaults to one second, `None` emits every log.
+    pub fn set_log_repeat_interval(mut self, interval: Option<Duration>) -> Self {
+        self.log_repeat_interval = interval;
+        self
+    }
+
+    /// Set the number of log records kept per mod in the `ModLogBuffer`
+    ///
+    /// Defaults to 256, 0 keeps no records.
+    pub fn set_log_buffer_capacity(mut self, capacity: usize) -> Self {
+        self.log_buffer_capacity = capacity;
+        self
+    }
+
+    /// Set the preferred format of the data exchanged with mods
+    ///
+    /// Mods not supporting it fall back to bincode. Defaults to bincode, the other formats
+    /// need the `postcard` or `msgpack` feature.
+    pub fn set_format(mut self, format: Format) -> Self {
+        if !format.is_supported() {
+            warn!("Format {} is not enabled, mods will use bincode", format);
+        }
+        self.format = format;
+        self
+    }
+
+    /// Get the minimum level of the logs of a mod
+    ///
+    /// The level set for the mod comes first, then the one of its manifest, then the
+    /// default level.
+    pub fn log_level(&self, mod_name: &str, manifest: Option<&ModManifest>) -> LogLevel {
+        self.mod_log_levels
+            .get(mod_name)
+            .copied()
+            .or_else(|| manifest.and_then(|manifest| manifest.log_level))
+            .unwrap_or(self.default_log_level)
+    }
 }
 
 impl Plugin for WasmModPlugin {
     fn build(&self, app: &mut App) {
+        // Ids registered twice shadow each other
+        report_duplicate_ids();
+
+        // Create the shared engine
+        let engine = match ModEngine::new(&self.engine_config, self.fuel_metering) {
+            Ok(engine) => engine,
+            Err(e) => {
+                error!("Failed to create wasm engine, using default config: {}", e);
+                ModEngine::new(&ModEngineConfig::default(), self.fuel_metering)
+                    .expect("Failed to create wasm engine")
+            }
+        };
+
         // Insert mod resource
         app.insert_resource(self.clone())
+            .insert_resource(engine)
             .insert_resource(LoadedMods(HashMap::new()))
-            .insert_resource(ModSystems(Vec::new()));
+            .insert_resource(ModSystems(Vec::new()))
+            .insert_resource(ModLogBuffer::new(self.log_buffer_capacity))
+            .init_resource::<ModSystemStates>()
+            .init_resource::<ModDefinedComponents>()
+            .init_resource::<ModDefinedResources>()
+            .init_resource::<ModStorage>()
+            .init_resource::<DiagnosticsStore>();
+
+        // Expose the assets of mod packages through the `mod://` asset source
+        let packages = ModPackages::default();
+        // Asset sources are read when the AssetPlugin is built, they cannot be added later
+        assert!(
+            !app.world().contains_resource::<AssetServer>(),
+            "WasmModPlugin must be added before AssetPlugin and DefaultPlugins, \
+             to register the `{}://` asset source of mod packages",
+            MOD_ASSET_SOURCE
+        );
+        let reader_packages = packages.clone();
+        app.register_asset_source(
+            MOD_ASSET_SOURCE,
+            AssetSourceBuilder::default().with_reader(move || Box::new(reader_packages.reader())),
+        );
+        app.insert_resource(packages)
+            .init_resource::<ModAssetHandles>();
+
+        app.init_resource::<ModLoadingState>()
+            .init_resource::<ModLoadingTasks>()
+            .add_event::<ModLoadingEvent>();
 
-        app.add_systems(PreStartup, load_all_mod);
+        app.add_systems(PreStartup, start_mod_loading);
         app.add_systems(Startup, load_world);
-        app.add_systems(PostStartup, execute_mod_startup_systems);
-        app.add_systems(PostUpdate, execute_mod_update_systems);
+        app.add_systems(PreUpdate, poll_mod_loading);
+        app.add_systems(
+            PostUpdate,
+            (execute_mod_startup_systems, execute_mod_update_systems).chain(),
+        );
+    }
+
+    fn finish(&self, app: &mut App) {
+        // Mods can only be assets when the asset plugin is added
+        if !app.world().contains_resource::<AssetServer>() {
+            if !self.mod_assets.is_empty() {
+                error!("AssetPlugin is required to load mod assets: {:?}", self.mod_assets);
+            }
+            return;
+        }
+
+        let engine = app.world().resource::<ModEngine>().clone();
+        let packages = app.world().resource::<ModPackages>().clone();
+        app.init_asset::<WasmMod>()
+            .register_asset_loader(WasmModLoader::new(engine, packages))
+            .init_resource::<ModAssets>();
+
+        // Asset types defined by mods, which Bevy has no loader for
+        app.init_asset::<TextAsset>()
+            .init_asset_loader::<TextAssetLoader>();
+
+        // Files read by mods
+        app.init_asset::<BytesAsset>()
+            .init_asset_loader::<BytesAssetLoader>();
+
+        app.add_systems(PreStartup, load_mod_assets);
+        app.add_systems(PreUpdate, instantiate_mod_assets.before(poll_mod_loading));
     }
 }
 
@@ -116,6 +419,8 @@ pub struct LoadedMod {
     pub instance: Instance,
     /// The WASM store
     pub store: Arc<RwLock<Store<ModState>>>,
+    /// Manifest of the mod, if loaded from a package
+    pub manifest: Option<ModManifest>,
 }
 
 /// Wasm state of mod
@@ -126,6 +431,26 @@ pub struct ModState {
     world: Option<Arc<UnsafeWorldCell<'static>>>,
     /// Ref to fn call while insert new asset
     new_asset_fn: Option<fn(&mut World, AssetInfo) -> String>,
+    /// Name of the mod, known once the mod is instantiated
+    mod_name: Option<String>,
+    /// Host access granted to the mod
+    permissions: ModPermissions,
+    /// Name of the system running, if any
+    current_system: Option<String>,
+    /// Minimum level of the logs of the mod
+    log_level: LogLevel,
+    /// Format of the data exchanged with the mod
+    format: Format,
+    /// Collapses the repeated logs of the mod
+    log_limiter: Option<LogRateLimiter>,
+    /// Components and resources whose schema differs from the host
+    schema_mismatches: HashSet<(SchemaKind, String)>,
+    /// System state changes requested by the mod
+    system_state_requests: Vec<SystemStateRequest>,
+    /// Whether world writes are deferred, set while the mod runs in parallel with other mods
+    deferred: bool,
+    /// World writes deferred while running in parallel
+    deferred_writes: Vec<DeferredWrite>,
 }
 
 impl ModState {
@@ -134,6 +459,16 @@ impl ModState {
             wasi_ctx: Arc::new(Mutex::new(UnsafeCell::new(wasi_ctx))),
             world: None,
             new_asset_fn: None,
+            mod_name: None,
+            permissions: ModPermissions::default(),
+            current_system: None,
+            log_level: LogLevel::Trace,
+            format: Format::Bincode,
+            log_limiter: None,
+            schema_mismatches: HashSet::new(),
+            system_state_requests: Vec::new(),
+            deferred: false,
+            deferred_writes: Vec::new(),
         }
     }
 
@@ -150,225 +485,151 @@ impl ModState {
 
     /// Get the Bevy world reference
     pub fn get_world(&self) -> Option<UnsafeWorldCell<'static>> {
-        match self.world.clone() {
-            Some(world) => Some(*world),
-            None => None,
-        }
+        self.world.as_ref().map(|world| **world)
     }
 
-    /// Set new asset fn
-    pub fn set_new_asset_fn(&mut self, func: fn(&mut World, AssetInfo) -> String) {
-        self.new_asset_fn = Some(func);
+    /// Set the mod name
+    pub fn set_mod_name(&mut self, name: impl Into<String>) {
+        self.mod_name = Some(name.into());
     }
 
-    /// Run new asset fn
-    pub fn run_new_asset_fn(&self, world: &mut World, asset_info: AssetInfo) -> anyhow::Result<String> {
-        match self.new_asset_fn {
-            Some(func) => {
-                let asset_id = (func)(world, asset_info);
-                anyhow::Ok(asset_id)
-            }
-            None => Err(anyhow::anyhow!("Handle new asset fn is none")),
-        }
+    /// Get the mod name
+    pub fn get_mod_name(&self) -> Option<&str> {
+        self.mod_name.as_deref()
     }
-}
 
-/// load all mod from mod paths
-fn load_all_mod(
-    r_mod: Res<WasmModPlugin>,
-    mut r_loaded_mods: ResMut<LoadedMods>,
-    mut r_mod_systems: ResMut<ModSystems>,
-) {
-    info!("loading mods: {:?}", r_mod.mod_paths);
+    /// Set the permissions of the mod
+    pub fn set_permissions(&mut self, permissions: ModPermissions) {
+        self.permissions = permissions;
+    }
 
-    let engine = Engine::default();
+    /// Get the permissions of the mod
+    pub fn permissions(&self) -> &ModPermissions {
+        &self.permissions
+    }
 
-    for mod_path in &r_mod.mod_paths {
-        // Load the WASM module
-        let module = match Module::from_file(&engine, mod_path) {
-            Ok(module) => module,
-            Err(e) => {
-                error!("Failed to load mod '{}': {}", mod_path, e);
-                continue;
-            }
-        };
+    /// Set the name of the system running
+    pub fn set_current_system(&mut self, system: Option<String>) {
+        self.current_system = system;
+    }
 
-        let mut linker: Linker<ModState> = Linker::new(&engine);
-        match wasmtime_wasi::preview1::add_to_linker_sync(&mut linker, |state| {
-            state.get_wasi_ctx_mut()
-        }) {
-            Ok(_) => {}
-            Err(e) => {
-                error!("Link wasi for mod '{}' faild: {}", mod_path, e);
-                continue;
-            }
-        }
-        match linker.func_wrap("env", "__mod_log", host_handle_log) {
-            Ok(_) => {}
-            Err(e) => {
-                error!("Error in link mod '{}' __mod_log: {}", mod_path, e);
-            }
-        };
+    /// Get the name of the system running, if any
+    pub fn current_system(&self) -> Option<&str> {
+        self.current_system.as_deref()
+    }
 
-        // Add query components function
-        match linker.func_wrap(
-            "env",
-            "__mod_query_components",
-            host_handle_query_components,
-        ) {
-            Ok(_) => {}
-            Err(e) => {
-                error!(
-                    "Error in link mod '{}' __mod_query_components: {}",
-                    mod_path, e
-                );
-            }
-        };
+    /// Set the minimum level of the logs of the mod
+    pub fn set_log_level(&mut self, level: LogLevel) {
+        self.log_level = level;
+    }
 
-        // Add query resources function
-        match linker.func_wrap("env", "__mod_query_resources", host_handle_query_resources) {
-            Ok(_) => {}
-            Err(e) => {
-                error!(
-                    "Error in link mod '{}' __mod_query_resources: {}",
-                    mod_path, e
-                );
-            }
-        };
+    /// Get the minimum level of the logs of the mod
+    pub fn log_level(&self) -> LogLevel {
+        self.log_level
+    }
 
-        // Add free memory function
-        match linker.func_wrap("env", "__mod_free_memory", host_handle_free_memory) {
-            Ok(_) => {}
-            Err(e) => {
-                error!("Error in link mod '{}' __mod_free_memory: {}", mod_path, e);
-            }
-        };
+    /// Set the format of the data exchanged with the mod
+    pub fn set_format(&mut self, format: Format) {
+        self.format = format;
+    }
 
-        // Add spawn entities function
-        match linker.func_wrap("env", "__mod_spawn_entities", host_handle_spawn_entities) {
-            Ok(_) => {}
-            Err(e) => {
-                error!(
-                    "Error in link mod '{}' __mod_spawn_entities: {}",
-                    mod_path, e
-                );
-            }
-        };
+    /// Get the format of the data exchanged with the mod
+    pub fn format(&self) -> Format {
+        self.format
+    }
 
-        // Add define asset function
-        match linker.func_wrap("env", "__mod_define_asset", host_handle_define_asset) {
-            Ok(_) => {}
-            Err(e) => {
-                error!("Error in link mod '{}' __mod_define_asset: {}", mod_path, e);
-            }
-        };
+    /// Set the limiter collapsing the repeated logs of the mod
+    pub fn set_log_limiter(&mut self, limiter: Option<LogRateLimiter>) {
+        self.log_limiter = limiter;
+    }
 
-        // wasi ctx
-        let mut mod_state = ModState::new(
-            WasiCtxBuilder::new()
-                .inherit_env()
-                .args(&[mod_path])
-                .build_p1(),
-        );
-        mod_state.set_new_asset_fn(r_mod.new_asset_fn);
-
-        // Create a store
-        let store = Store::new(&engine, mod_state);
-        let store_arc = Arc::new(RwLock::new(store));
-
-        // Instantiate the module
-        let instance = {
-            let mut store = store_arc.write().unwrap();
-            match linker.instantiate(&mut *store, &module) {
-                Ok(instance) => instance,
-                Err(e) => {
-                    error!("Failed to instantiate mod '{}': {}", mod_path, e);
-                    continue;
-                }
-            }
-        };
+    /// Get the limiter collapsing the repeated logs of the mod
+    pub fn log_limiter_mut(&mut self) -> Option<&mut LogRateLimiter> {
+        self.log_limiter.as_mut()
+    }
 
-        // Try to get the mod name
-        let mod_name = {
-            let mut store = store_arc.write().unwrap();
-            match get_mod_name(&mut *store, &instance) {
-                Ok(name) => {
-                    info!("Mod name: '{}'", name);
-                    name
-                }
-                Err(e) => {
-                    error!("Failed to get mod name: {}", e);
-                    "unnamed_mod".to_string()
-                }
-            }
-        };
+    /// Mark a component or resource whose schema differs from the host
+    pub(crate) fn add_schema_mismatch(&mut self, kind: SchemaKind, id: impl Into<String>) {
+        self.schema_mismatches.insert((kind, id.into()));
+    }
 
-        // Get the systems names from the instance
-        let systems = {
-            let mut store = store_arc.write().unwrap();
-            match get_systems(&mut *store, &instance) {
-                Ok(systems) => {
-                    info!("Get systems: {:?}", systems);
-                    systems
-                }
-                Err(e) => {
-                    error!("Failed to get systems: {}", e);
-                    continue;
-                }
-            }
-        };
+    /// Whether the schema of a component or resource differs from the host
+    ///
+    /// The data of such types is not exchanged with the mod.
+    pub fn has_schema_mismatch(&self, kind: SchemaKind, id: &str) -> bool {
+        !self.schema_mismatches.is_empty()
+            && self.schema_mismatches.contains(&(kind, id.to_string()))
+    }
 
-        // Get system info for each system
-        let mut system_infos = HashMap::new();
-        for system_name in &systems {
-            let info = {
-                let mut store = store_arc.write().unwrap();
-                match get_mod_system_info(&mut *store, &instance, system_name) {
-                    Ok(info) => info,
-                    Err(e) => {
-                        error!("Failed to get system info for '{}': {}", system_name, e);
-                        continue;
-                    }
-                }
-            };
-
-            let export_name = system_info_export_name_str(&info);
-            info!(
-                "System info for '{}': export_name = '{}', schedule = {}",
-                system_name, &export_name, info.schedule
-            );
-
-            let func = {
-                let mut store = store_arc.write().unwrap();
-                match instance.get_typed_func::<(), ()>(&mut *store, &export_name) {
-                    Ok(func) => func,
-                    Err(e) => {
-                        error!(
-                            "Failed to get function '{}' for system '{}': {}",
-                            export_name, system_name, e
-                        );
-                        continue;
-                    }
-                }
-            };
-
-            r_mod_systems.0.push(ModSystemInfo {
-                mod_name: mod_name.clone(),
-                schedule: ModSystemSchedule::from(info.schedule),
-                run_func: func,
-            });
-            system_infos.insert(system_name.clone(), info);
-        }
+    /// Queue a system state change requested by the mod
+    pub(crate) fn push_system_state_request(&mut self, request: SystemStateRequest) {
+        self.system_state_requests.push(request);
+    }
 
-        // Create a LoadedMod resource
-        let loaded_mod = LoadedMod {
-            system_infos,
-            instance,
-            store: store_arc,
-        };
+    /// Take the queued system state changes
+    pub(crate) fn take_system_state_requests(&mut self) -> Vec<SystemStateRequest> {
+        std::mem::take(&mut self.system_state_requests)
+    }
+
+    /// Whether world writes are deferred
+    pub fn is_deferred(&self) -> bool {
+        self.deferred
+    }
+
+    /// Set whether world writes are deferred
+    pub(crate) fn set_deferred(&mut self, deferred: bool) {
+        self.deferred = deferred;
+    }
 
-        // Add the loaded mod
-        r_loaded_mods.0.insert(mod_name, loaded_mod);
+    /// Queue a deferred world write
+    pub(crate) fn push_deferred_write(&mut self, write: DeferredWrite) {
+        self.deferred_writes.push(write);
+    }
+
+    /// Take the deferred world writes
+    pub(crate) fn take_deferred_writes(&mut self) -> Vec<DeferredWrite> {
+        std::mem::take(&mut self.deferred_writes)
+    }
+
+    /// Set new asset fn
+    pub fn set_new_asset_fn(&mut self, func: fn(&mut World, AssetInfo) -> String) {
+        self.new_asset_fn = Some(func);
+    }
+
+    /// Run new asset fn
+    pub fn run_new_asset_fn(&self, world: &mut World, asset_info: AssetInfo) -> anyhow::Result<String> {
+        match self.new_asset_fn {
+            Some(func) => {
+                let asset_id = (func)(world, asset_info);
+                anyhow::Ok(asset_id)
+            }
+            None => Err(anyhow::anyhow!("Handle new asset fn is none")),
+        }
+    }
+}
+
+/// Log the component and resource ids registered by more than one type
+fn report_duplicate_ids() {
+    let duplicates = duplicate_component_ids()
+        .into_iter()
+        .map(|duplicate| ("Component", "mod_component", duplicate))
+        .chain(
+            duplicate_resource_ids()
+                .into_iter()
+                .map(|duplicate| ("Resource", "mod_resource", duplicate)),
+        );
+    for (kind, attribute, duplicate) in duplicates {
+        error!(
+            "{} id '{}' is registered by both `{}` and `{}`, mods can only reach `{}`. \
+             Give them different ids, or namespace them with `#[{}(id = \"{}\", namespaced)]`",
+            kind,
+            duplicate.id,
+            duplicate.first,
+            duplicate.second,
+            duplicate.first,
+            attribute,
+            duplicate.id
+        );
     }
 }
 
@@ -398,23 +659,31 @@ fn load_world(world: &mut World) {
 
     info!("loading world for mods {:?}", loaded_mod_keys);
 
-    // Create a static reference to the world
-    // This is safe because the world reference is valid for the duration of the application
-    let world_cell = unsafe {
-        std::mem::transmute::<UnsafeWorldCell<'_>, UnsafeWorldCell<'static>>(
-            world.as_unsafe_world_cell(),
-        )
-    };
+    let world_cell = static_world_cell(world);
 
     // Update each loaded mod
     let r_loaded_mods = world.get_resource::<LoadedMods>().unwrap();
     for mod_name in &loaded_mod_keys {
         if let Some(loaded_mod) = r_loaded_mods.0.get(mod_name) {
-            let store_arc = loaded_mod.store.clone();
-            let mut store = store_arc.write().unwrap();
-            store.data_mut().set_world(Arc::new(world_cell));
+            set_mod_world(world_cell, loaded_mod);
         } else {
             error!("Faild to load world {}", mod_name);
         }
     }
 }
+
+/// Create a static reference to the world
+pub(crate) fn static_world_cell(world: &mut World) -> UnsafeWorldCell<'static> {
+    // This is safe because the world reference is valid for the duration of the application
+    unsafe {
+        std::mem::transmute::<UnsafeWorldCell<'_>, UnsafeWorldCell<'static>>(
+            world.as_unsafe_world_cell(),
+        )
+    }
+}
+
+/// Update the world reference in the state of a mod
+pub(crate) fn set_mod_world(world_cell: UnsafeWorldCell<'static>, loaded_mod: &LoadedMod) {
+    let mut store = loaded_mod.store.write().unwrap();
+    store.data_mut().set_world(Arc::new(world_cell));
+}
@@ -0,0 +1,730 @@
+//! Asynchronous mod loading
+//!
+//! Mods are compiled and instantiated on the `AsyncComputeTaskPool`, so loading a big mod
+//! set does not stall the first frames. The progress of each mod is tracked in the
+//! [`ModLoadingState`] resource and reported through [`ModLoadingEvent`]s. The Startup
+//! systems of a mod run once it has finished loading.
+
+use crate::diagnostic::register_mod_diagnostics;
+use crate::dynamic::{register_mod_components, register_mod_resources};
+use crate::engine::ModEngine;
+use crate::package::{ModManifest, ModPackage, ModPackages, is_package_path};
+use crate::system::{ModSystemInfo, ModSystemSchedule};
+use crate::utils::*;
+use crate::*;
+use bevy::diagnostic::DiagnosticsStore;
+use bevy::tasks::{AsyncComputeTaskPool, Task, TaskPool, block_on, futures_lite::future};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use wasmtime::{Linker, Module, Store};
+use wasmtime_wasi::WasiCtxBuilder;
+
+/// Loading status of a mod
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModLoadStatus {
+    /// Waiting for the loading task to start
+    Pending,
+    /// Compiling the wasm module
+    Compiling,
+    /// Instantiated and ready to run
+    Instantiated,
+    /// Failed to load, with the reason
+    Failed(String),
+}
+
+impl ModLoadStatus {
+    /// Whether loading is over, successfully or not
+    pub fn is_finished(&self) -> bool {
+        matches!(self, ModLoadStatus::Instantiated | ModLoadStatus::Failed(_))
+    }
+}
+
+/// Loading progress of one mod
+#[derive(Debug, Clone)]
+pub struct ModLoadEntry {
+    /// Path of the mod
+    pub path: String,
+    /// Name of the mod, known once it is instantiated
+    pub mod_name: Option<String>,
+    /// Loading status
+    pub status: ModLoadStatus,
+}
+
+/// Resource with the loading progress of all mods
+#[derive(Resource, Debug, Clone, Default)]
+pub struct ModLoadingState {
+    /// Mods in load order
+    mods: Vec<ModLoadEntry>,
+    /// Mods whose Startup systems have not run yet
+    startup_pending: HashSet<String>,
+}
+
+impl ModLoadingState {
+    /// Get the loading progress of a mod by path
+    pub fn get(&self, path: &str) -> Option<&ModLoadEntry> {
+        self.mods.iter().find(|entry| entry.path == path)
+    }
+
+    /// Iterate over the loading progress of all mods
+    pub fn iter(&self) -> impl Iterator<Item = &ModLoadEntry> {
+        self.mods.iter()
+    }
+
+    /// Number of mods finished loading, and total number of mods
+    pub fn progress(&self) -> (usize, usize) {
+        let finished = self
+            .mods
+            .iter()
+            .filter(|entry| entry.status.is_finished())
+            .count();
+        (finished, self.mods.len())
+    }
+
+    /// Whether all mods finished loading
+    pub fn is_finished(&self) -> bool {
+        self.mods.iter().all(|entry| entry.status.is_finished())
+    }
+
+    /// Set the status of a mod, returning whether it changed
+    pub(crate) fn set_status(&mut self, path: &str, status: ModLoadStatus) -> bool {
+        match self.mods.iter_mut().find(|entry| entry.path == path) {
+            Some(entry) if entry.status != status => {
+                entry.status = status;
+                true
+            }
+            Some(_) => false,
+            None => {
+                self.mods.push(ModLoadEntry {
+                    path: path.to_string(),
+                    mod_name: None,
+                    status,
+                });
+                true
+            }
+        }
+    }
+
+    /// Take the mods whose Startup systems have not run yet
+    pub(crate) fn take_startup_pending(&mut self) -> HashSet<String> {
+        std::mem::take(&mut self.startup_pending)
+    }
+}
+
+/// Event sent when the loading status of a mod changes
+#[derive(Event, Debug, Clone)]
+pub struct ModLoadingEvent {
+    /// Path of the mod
+    pub path: String,
+    /// New loading status
+    pub status: ModLoadStatus,
+    /// Number of mods finished loading
+    pub finished: usize,
+    /// Total number of mods
+    pub total: usize,
+}
+
+/// A mod loaded by a task, waiting to be added to the world
+pub(crate) struct LoadedModData {
+    /// Name of the mod
+    mod_name: String,
+    /// The loaded mod
+    loaded_mod: LoadedMod,
+    /// Systems of the mod
+    systems: Vec<ModSystemInfo>,
+    /// Components defined by the mod
+    components: Vec<ModComponentDef>,
+    /// Resources defined by the mod
+    resources: Vec<ModResourceDef>,
+    /// Format of the data exchanged with the mod
+    format: Format,
+}
+
+/// A running mod loading task
+pub(crate) struct ModLoadingTask {
+    /// Path of the mod
+    path: String,
+    /// Set by the task once it starts compiling
+    compiling: Arc<AtomicBool>,
+    /// The task
+    task: Task<Result<LoadedModData, String>>,
+}
+
+/// Resource with the running mod loading tasks
+#[derive(Resource, Default)]
+pub(crate) struct ModLoadingTasks(Vec<ModLoadingTask>);
+
+impl ModLoadingTasks {
+    /// Start instantiating an already compiled mod
+    pub(crate) fn instantiate(
+        &mut self,
+        path: String,
+        module: Module,
+        manifest: Option<ModManifest>,
+        plugin: &WasmModPlugin,
+        engine: &ModEngine,
+    ) {
+        let task_pool = AsyncComputeTaskPool::get_or_init(TaskPool::default);
+        let task = {
+            let mod_path = path.clone();
+            let plugin = plugin.clone();
+            let engine = engine.clone();
+            task_pool.spawn(async move {
+                instantiate_mod(&mod_path, &module, manifest, &plugin, &engine)
+            })
+        };
+        self.0.push(ModLoadingTask {
+            path,
+            compiling: Arc::new(AtomicBool::new(false)),
+            task,
+        });
+    }
+}
+
+/// Start loading all mods from mod paths
+pub(crate) fn start_mod_loading(
+    r_mod: Res<WasmModPlugin>,
+    r_engine: Res<ModEngine>,
+    r_packages: Res<ModPackages>,
+    mut r_tasks: ResMut<ModLoadingTasks>,
+    mut r_state: ResMut<ModLoadingState>,
+    mut ev_loading: EventWriter<ModLoadingEvent>,
+) {
+    info!("loading mods: {:?}", r_mod.mod_paths);
+
+    let task_pool = AsyncComputeTaskPool::get_or_init(TaskPool::default);
+    for mod_path in &r_mod.mod_paths {
+        r_state.set_status(mod_path, ModLoadStatus::Pending);
+
+        let compiling = Arc::new(AtomicBool::new(false));
+        let task = {
+            let mod_path = mod_path.clone();
+            let plugin = r_mod.clone();
+            let engine = r_engine.clone();
+            let packages = r_packages.clone();
+            let compiling = compiling.clone();
+            task_pool.spawn(async move {
+                compiling.store(true, Ordering::Release);
+                load_mod(&mod_path, &plugin, &engine, &packages)
+            })
+        };
+        r_tasks.0.push(ModLoadingTask {
+            path: mod_path.clone(),
+            compiling,
+            task,
+        });
+    }
+
+    let (finished, total) = r_state.progress();
+    for entry in r_state.iter() {
+        ev_loading.write(ModLoadingEvent {
+            path: entry.path.clone(),
+            status: entry.status.clone(),
+            finished,
+            total,
+        });
+    }
+}
+
+/// Poll the mod loading tasks, adding the loaded mods to the world
+pub(crate) fn poll_mod_loading(world: &mut World) {
+    let tasks = std::mem::take(&mut world.resource_mut::<ModLoadingTasks>().0);
+    if tasks.is_empty() {
+        return;
+    }
+
+    let mut running = Vec::new();
+    let mut changes = Vec::new();
+    for mut loading_task in tasks {
+        match block_on(future::poll_once(&mut loading_task.task)) {
+            Some(Ok(data)) => {
+                add_loaded_mod(world, &loading_task.path, data);
+                changes.push((loading_task.path, ModLoadStatus::Instantiated));
+            }
+            Some(Err(e)) => {
+                error!("{}", e);
+                changes.push((loading_task.path, ModLoadStatus::Failed(e)));
+            }
+            None => {
+                if loading_task.compiling.load(Ordering::Acquire) {
+                    changes.push((loading_task.path.clone(), ModLoadStatus::Compiling));
+                }
+                running.push(loading_task);
+            }
+        }
+    }
+    world.resource_mut::<ModLoadingTasks>().0 = running;
+
+    // Update the loading state and report the changes
+    let mut r_state = world.resource_mut::<ModLoadingState>();
+    let changes: Vec<_> = changes
+        .into_iter()
+        .filter(|(path, status)| r_state.set_status(path, status.clone()))
+        .collect();
+    let (finished, total) = r_state.progress();
+    for (path, status) in changes {
+        world.send_event(ModLoadingEvent {
+            path,
+            status,
+            finished,
+            total,
+        });
+    }
+}
+
+/// Add a mod loaded by a task to the world
+fn add_loaded_mod(world: &mut World, path: &str, data: LoadedModData) {
+    let LoadedModData {
+        mod_name,
+        loaded_mod,
+        systems,
+        components,
+        resources,
+        format,
+    } = data;
+
+    // A reloaded mod replaces its previous instance
+    let previous = world
+        .resource::<ModLoadingState>()
+        .get(path)
+        .and_then(|entry| entry.mod_name.clone());
+    if let Some(previous) = previous {
+        info!("Reloading mod '{}'", previous);
+        unload_mod(world, &previous);
+    }
+
+    // Register the components and resources of the mod before its systems use them
+    register_mod_components(world, &mod_name, components);
+    register_mod_resources(world, &mod_name, resources, format);
+
+    // Give the mod access to the world
+    set_mod_world(static_world_cell(world), &loaded_mod);
+
+    // Register the diagnostics of the mod
+    let fuel_metering = world.resource::<WasmModPlugin>().fuel_metering;
+    let system_names: Vec<String> = systems.iter().map(|s| s.system_name.clone()).collect();
+    register_mod_diagnostics(
+        &mut world.resource_mut::<DiagnosticsStore>(),
+        &mod_name,
+        &system_names,
+        fuel_metering,
+    );
+
+    world.resource_mut::<ModSystems>().0.extend(systems);
+    world
+        .resource_mut::<LoadedMods>()
+        .0
+        .insert(mod_name.clone(), loaded_mod);
+
+    let mut r_state = world.resource_mut::<ModLoadingState>();
+    if let Some(entry) = r_state.mods.iter_mut().find(|entry| entry.path == path) {
+        entry.mod_name = Some(mod_name.clone());
+    }
+    r_state.startup_pending.insert(mod_name);
+}
+
+/// Command unloading a mod
+///
+/// The systems of the mod stop running, the assets of its package are unmounted and the
+/// assets it holds are released.
+pub struct UnloadMod(pub String);
+
+impl Command for UnloadMod {
+    fn apply(self, world: &mut World) {
+        let mod_name = self.0;
+        if !world.resource::<LoadedMods>().0.contains_key(&mod_name) {
+            warn!("Cannot unload mod '{}': not loaded", mod_name);
+            return;
+        }
+        info!("Unloading mod '{}'", mod_name);
+
+        // Packages are mounted under the name of their manifest
+        let package_name = world.resource::<LoadedMods>().0[&mod_name]
+            .manifest
+            .as_ref()
+            .map(|manifest| manifest.name.clone());
+        unload_mod(world, &mod_name);
+        if let Some(package_name) = package_name {
+            world.resource::<ModPackages>().unmount(&package_name);
+        }
+        world
+            .resource_mut::<ModLoadingState>()
+            .mods
+            .retain(|entry| entry.mod_name.as_deref() != Some(mod_name.as_str()));
+    }
+}
+
+/// Remove a loaded mod, its systems and its assets from the world
+pub(crate) fn unload_mod(world: &mut World, mod_name: &str) {
+    world
+        .resource_mut::<ModAssetHandles>()
+        .release_mod(mod_name);
+    world
+        .resource_mut::<ModSystems>()
+        .0
+        .retain(|system| system.mod_name != mod_name);
+    world.resource_mut::<LoadedMods>().0.remove(mod_name);
+    world
+        .resource_mut::<ModLoadingState>()
+        .startup_pending
+        .remove(mod_name);
+}
+
+/// Load a mod: compile, link and instantiate it, then read its systems
+///
+/// Mod packages also get their assets mounted.
+fn load_mod(
+    mod_path: &str,
+    plugin: &WasmModPlugin,
+    mod_engine: &ModEngine,
+    packages: &ModPackages,
+) -> Result<LoadedModData, String> {
+    if !is_package_path(mod_path) {
+        // Load the WASM module
+        let module = mod_engine
+            .load_module(mod_path)
+            .map_err(|e| format!("Failed to load mod '{}': {}", mod_path, e))?;
+
+        return instantiate_mod(mod_path, &module, None, plugin, mod_engine);
+    }
+
+    // Load the mod package
+    let package = ModPackage::from_file(mod_path)
+        .map_err(|e| format!("Failed to read mod package '{}': {}", mod_path, e))?;
+    let module = mod_engine
+        .compile_module(&package.module)
+        .map_err(|e| format!("Failed to load mod '{}': {}", mod_path, e))?;
+    packages.mount(&package);
+
+    instantiate_mod(mod_path, &module, Some(package.manifest), plugin, mod_engine)
+}
+
+/// Instantiate a compiled mod, then read its systems
+fn instantiate_mod(
+    mod_path: &str,
+    module: &Module,
+    manifest: Option<ModManifest>,
+    plugin: &WasmModPlugin,
+    mod_engine: &ModEngine,
+) -> Result<LoadedModData, String> {
+    let engine = mod_engine.engine();
+
+    let linker = create_linker(engine, mod_path)?;
+
+    // wasi ctx
+    let mut mod_state = ModState::new(
+        WasiCtxBuilder::new()
+            .inherit_env()
+            .args(&[mod_path])
+            .build_p1(),
+    );
+    mod_state.set_new_asset_fn(plugin.new_asset_fn);
+
+    // Create a store
+    let mut store = Store::new(engine, mod_state);
+    if plugin.fuel_metering {
+        // Fuel is only measured, never exhausted
+        if let Err(e) = store.set_fuel(u64::MAX) {
+            error!("Failed to set fuel for mod '{}': {}", mod_path, e);
+        }
+    }
+
+    // Instantiate the module
+    let instance = linker
+        .instantiate(&mut store, module)
+        .map_err(|e| format!("Failed to instantiate mod '{}': {}", mod_path, e))?;
+
+    // Try to get the mod name
+    let mod_name = match get_mod_name(&mut store, &instance) {
+        Ok(name) => {
+            info!("Mod name: '{}'", name);
+            name
+        }
+        Err(e) => {
+            error!("Failed to get mod name: {}", e);
+            "unnamed_mod".to_string()
+        }
+    };
+    store.data_mut().set_mod_name(mod_name.clone());
+    store
+        .data_mut()
+        .set_permissions(plugin.permissions(&mod_name).clone());
+
+    // Filter the logs of the mod, in the mod itself when it supports it
+    let log_level = plugin.log_level(&mod_name, manifest.as_ref());
+    store.data_mut().set_log_level(log_level);
+    store
+        .data_mut()
+        .set_log_limiter(plugin.log_repeat_interval.map(LogRateLimiter::new));
+    if let Err(e) = set_mod_log_level(&mut store, &instance, log_level) {
+        warn!("Mod '{}' filters its logs on the host only: {}", mod_name, e);
+    }
+
+    // Agree on the format of the exchanged data before calling into the mod
+    let format = negotiate_mod_format(&mut store, &instance, plugin.format).unwrap_or_else(|e| {
+        warn!("Mod '{}' uses bincode, format negotiation failed: {}", mod_name, e);
+        Format::Bincode
+    });
+    if format != plugin.format {
+        info!("Mod '{}' does not support {}, using {}", mod_name, plugin.format, format);
+    }
+    store.data_mut().set_format(format);
+
+    // Get the components and resources defined by the mod
+    let components = get_mod_definitions(&mut store, &instance, "__mod_components", format)
+        .unwrap_or_else(|e| {
+            error!("Failed to get the components defined by mod '{}': {}", mod_name, e);
+            Vec::new()
+        });
+    let resources = get_mod_definitions(&mut store, &instance, "__mod_resources", format)
+        .unwrap_or_else(|e| {
+            error!("Failed to get the resources defined by mod '{}': {}", mod_name, e);
+            Vec::new()
+        });
+
+    // Package assets are found under the manifest name
+    if let Some(manifest) = &manifest
+        && manifest.name != mod_name
+    {
+        warn!(
+            "Mod '{}' is named '{}' in its manifest, its assets are in 'mod://{}'",
+            mod_name, manifest.name, manifest.name
+        );
+    }
+
+    // Get the systems names from the instance
+    let system_names = get_systems(&mut store, &instance)
+        .map_err(|e| format!("Failed to get systems of mod '{}': {}", mod_path, e))?;
+    info!("Get systems: {:?}", system_names);
+
+    // Get system info for each system
+    let mut system_infos = HashMap::new();
+    let mut systems = Vec::new();
+    for system_name in &system_names {
+        let info = match get_mod_system_info(&mut store, &instance, system_name) {
+            Ok(info) => info,
+            Err(e) => {
+                error!("Failed to get system info for '{}': {}", system_name, e);
+                continue;
+            }
+        };
+
+        let export_name = system_info_export_name_str(&info);
+        info!(
+            "System info for '{}': export_name = '{}', schedule = {}",
+            system_name, &export_name, info.schedule
+        );
+
+        let func = match instance.get_typed_func::<(), ()>(&mut store, &export_name) {
+            Ok(func) => func,
+            Err(e) => {
+                error!(
+                    "Failed to get function '{}' for system '{}': {}",
+                    export_name, system_name, e
+                );
+                continue;
+            }
+        };
+
+        // Get the access declared by the system
+        let access = match get_mod_system_access(&mut store, &instance, system_name, format) {
+            Ok(access) => access,
+            Err(e) => {
+                warn!("Failed to get access for system '{}': {}", system_name, e);
+                None
+            }
+        };
+
+        systems.push(ModSystemInfo {
+            mod_name: mod_name.clone(),
+            system_name: system_name.clone(),
+            schedule: ModSystemSchedule::from(info.schedule),
+            run_func: func,
+            access,
+        });
+        system_infos.insert(system_name.clone(), info);
+    }
+
+    Ok(LoadedModData {
+        mod_name,
+        loaded_mod: LoadedMod {
+            system_infos,
+            instance,
+            store: Arc::new(RwLock::new(store)),
+            manifest,
+        },
+        systems,
+        components,
+        resources,
+        format,
+    })
+}
+
+/// Create a linker with all host functions
+fn create_linker(engine: &wasmtime::Engine, mod_path: &str) -> Result<Linker<ModState>, String> {
+    let mut linker: Linker<ModState> = Linker::new(engine);
+    if let Err(e) =
+        wasmtime_wasi::preview1::add_to_linker_sync(&mut linker, |state| state.get_wasi_ctx_mut())
+    {
+        return Err(format!("Link wasi for mod '{}' faild: {}", mod_path, e));
+    }
+
+    // Add log function
+    match linker.func_wrap("env", "__mod_log", host_handle_log) {
+        Ok(_) => {}
+        Err(e) => {
+            error!("Error in link mod '{}' __mod_log: {}", mod_path, e);
+        }
+    };
+
+    // Add query components function
+    match linker.func_wrap(
+        "env",
+        "__mod_query_components",
+        host_handle_query_components,
+    ) {
+        Ok(_) => {}
+        Err(e) => {
+            error!(
+                "Error in link mod '{}' __mod_query_components: {}",
+                mod_path, e
+            );
+        }
+    };
+
+    // Add query resources function
+    match linker.func_wrap("env", "__mod_query_resources", host_handle_query_resources) {
+        Ok(_) => {}
+        Err(e) => {
+            error!(
+                "Error in link mod '{}' __mod_query_resources: {}",
+                mod_path, e
+            );
+        }
+    };
+
+    // Add fetch system params function
+    match linker.func_wrap(
+        "env",
+        "__mod_fetch_system_params",
+        host_handle_fetch_system_params,
+    ) {
+        Ok(_) => {}
+        Err(e) => {
+            error!(
+                "Error in link mod '{}' __mod_fetch_system_params: {}",
+                mod_path, e
+            );
+        }
+    };
+
+    // Add apply system writes function
+    match linker.func_wrap(
+        "env",
+        "__mod_apply_system_writes",
+        host_handle_apply_system_writes,
+    ) {
+        Ok(_) => {}
+        Err(e) => {
+            error!(
+                "Error in link mod '{}' __mod_apply_system_writes: {}",
+                mod_path, e
+            );
+        }
+    };
+
+    // Add free memory function
+    match linker.func_wrap("env", "__mod_free_memory", host_handle_free_memory) {
+        Ok(_) => {}
+        Err(e) => {
+            error!("Error in link mod '{}' __mod_free_memory: {}", mod_path, e);
+        }
+    };
+
+    // Add spawn entities function
+    match linker.func_wrap("env", "__mod_spawn_entities", host_handle_spawn_entities) {
+        Ok(_) => {}
+        Err(e) => {
+            error!(
+                "Error in link mod '{}' __mod_spawn_entities: {}",
+                mod_path, e
+            );
+        }
+    };
+
+    // Add set system enabled function
+    match linker.func_wrap(
+        "env",
+        "__mod_set_system_enabled",
+        host_handle_set_system_enabled,
+    ) {
+        Ok(_) => {}
+        Err(e) => {
+            error!(
+                "Error in link mod '{}' __mod_set_system_enabled: {}",
+                mod_path, e
+            );
+        }
+    };
+
+    // Add define asset function
+    match linker.func_wrap("env", "__mod_define_asset", host_handle_define_asset) {
+        Ok(_) => {}
+        Err(e) => {
+            error!("Error in link mod '{}' __mod_define_asset: {}", mod_path, e);
+        }
+    };
+
+    // Add load asset function
+    match linker.func_wrap("env", "__mod_load_asset", host_handle_load_asset) {
+        Ok(_) => {}
+        Err(e) => {
+            error!("Error in link mod '{}' __mod_load_asset: {}", mod_path, e);
+        }
+    };
+
+    // Add asset load state function
+    match linker.func_wrap("env", "__mod_asset_load_state", host_handle_asset_load_state) {
+        Ok(_) => {}
+        Err(e) => {
+            error!("Error in link mod '{}' __mod_asset_load_state: {}", mod_path, e);
+        }
+    };
+
+    // Add asset read function
+    match linker.func_wrap("env", "__mod_read_asset", host_handle_read_asset) {
+        Ok(_) => {}
+        Err(e) => {
+            error!("Error in link mod '{}' __mod_read_asset: {}", mod_path, e);
+        }
+    };
+
+    // Add schema check function
+    match linker.func_wrap("env", "__mod_check_schema", host_handle_check_schema) {
+        Ok(_) => {}
+        Err(e) => {
+            error!("Error in link mod '{}' __mod_check_schema: {}", mod_path, e);
+        }
+    };
+
+    // Add mod storage functions
+    match linker.func_wrap("env", "__mod_storage_get", host_handle_storage_get) {
+        Ok(_) => {}
+        Err(e) => {
+            error!("Error in link mod '{}' __mod_storage_get: {}", mod_path, e);
+        }
+    };
+    match linker.func_wrap("env", "__mod_storage_set", host_handle_storage_set) {
+        Ok(_) => {}
+        Err(e) => {
+            error!("Error in link mod '{}' __mod_storage_set: {}", mod_path, e);
+        }
+    };
+    match linker.func_wrap("env", "__mod_storage_remove", host_handle_storage_remove) {
+        Ok(_) => {}
+        Err(e) => {
+            error!("Error in link mod '{}' __mod_storage_remove: {}", mod_path, e);
+        }
+    };
+
+    Ok(linker)
+}
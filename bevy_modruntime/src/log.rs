@@ -1,35 +1,332 @@
-use bevy::log::{debug, error, info, warn};
+//! Logging for mods
+//!
+//! Log records sent by mods are emitted through `bevy::log`, with the name of the mod and
+//! of the running system as `mod_name` and `system` fields. The structured fields of a
+//! record are emitted as a `fields` field.
+//!
+//! Records below the log level of the mod are dropped, and identical records are emitted
+//! at most once per repeat interval, with the number of repeats in a `repeated` field.
+//!
+//! The emitted records are also kept in the [`ModLogBuffer`] resource, for in-game
+//! consoles and mod managers.
+
 use crate::ModState;
+use crate::utils::memory_range;
+use bevy::log::{debug, error, info, trace, warn};
+use bevy::prelude::{Real, Resource, Time};
+use bevy_modtypes::{LogLevel, LogRecord};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
 
-/// Handle log
-pub fn host_handle_log(
-    mut caller: wasmtime::Caller<'_, ModState>,
-    ptr: i32,
-    len: i32,
-    level: i32,
-) {
-    let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
-    let mem_data = memory.data(&mut caller);
-    let bytes = &mem_data[ptr as usize..(ptr + len) as usize];
-    let msg = String::from_utf8_lossy(bytes);
+/// Number of distinct records remembered before forgetting the old ones
+const MAX_TRACKED_RECORDS: usize = 1024;
+
+/// Collapses the repeated log records of a mod
+#[derive(Debug, Clone)]
+pub struct LogRateLimiter {
+    /// Minimum time between two identical records
+    interval: Duration,
+    /// Last emission and number of suppressed repeats, by hash of the record
+    seen: HashMap<u64, (Instant, u32)>,
+}
 
-    match level {
-        0 => {
-            debug!("{}", msg);
+impl LogRateLimiter {
+    /// Create a limiter emitting identical records at most once per interval
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            seen: HashMap::new(),
         }
-        1 => {
-            info!("{}", msg);
+    }
+
+    /// Check whether a record is emitted
+    ///
+    /// Returns the number of identical records suppressed since it was last emitted, or
+    /// `None` if this one is suppressed.
+    pub fn check(&mut self, record: &LogRecord, now: Instant) -> Option<u32> {
+        let mut hasher = DefaultHasher::new();
+        record.level.hash(&mut hasher);
+        record.message.hash(&mut hasher);
+        record.fields.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        if let Some((last, repeats)) = self.seen.get_mut(&hash) {
+            if now.duration_since(*last) < self.interval {
+                *repeats += 1;
+                return None;
+            }
+            let suppressed = *repeats;
+            *last = now;
+            *repeats = 0;
+            return Some(suppressed);
         }
-        2 => {
-            warn!("{}", msg);
+
+        // Forget the records not seen for a while, then the oldest one if all are recent
+        if self.seen.len() >= MAX_TRACKED_RECORDS {
+            let interval = self.interval;
+            self.seen
+                .retain(|_, (last, _)| now.duration_since(*last) < interval);
         }
-        3 => {
-            error!("{}", msg);
+        if self.seen.len() >= MAX_TRACKED_RECORDS {
+            let oldest = self
+                .seen
+                .iter()
+                .min_by_key(|(_, (last, _))| *last)
+                .map(|(hash, _)| *hash);
+            if let Some(oldest) = oldest {
+                self.seen.remove(&oldest);
+            }
         }
-        _ => {
-            info!("{}", msg);
+        self.seen.insert(hash, (now, 0));
+        Some(0)
+    }
+}
+
+/// Emit a log event at a level known at runtime
+macro_rules! log_event {
+    ($level:expr, $($field:tt)+) => {
+        match $level {
+            LogLevel::Trace => trace!($($field)+),
+            LogLevel::Debug => debug!($($field)+),
+            LogLevel::Info => info!($($field)+),
+            LogLevel::Warn => warn!($($field)+),
+            LogLevel::Error => error!($($field)+),
         }
+    };
+}
+
+/// A log record of a mod, kept in the [`ModLogBuffer`]
+#[derive(Debug, Clone)]
+pub struct ModLogEntry {
+    /// The level of the record
+    pub level: LogLevel,
+    /// Real time elapsed since the app started
+    pub timestamp: Duration,
+    /// The system running when the record was sent, if any
+    pub system: Option<String>,
+    /// The formatted message
+    pub message: String,
+    /// Structured fields as `(key, value)`
+    pub fields: Vec<(String, String)>,
+}
+
+/// Resource keeping the last log records of each mod
+///
+/// Records are added while mods run, possibly in parallel, so they are behind a lock and
+/// read as copies.
+#[derive(Resource, Debug)]
+pub struct ModLogBuffer {
+    /// Number of records kept per mod
+    capacity: usize,
+    /// Records of each mod, from the oldest to the newest
+    mods: Mutex<HashMap<String, VecDeque<ModLogEntry>>>,
+}
+
+impl Default for ModLogBuffer {
+    fn default() -> Self {
+        Self::new(256)
     }
 }
 
+impl ModLogBuffer {
+    /// Create a buffer keeping the last `capacity` records of each mod
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            mods: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the number of records kept per mod
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Add a record of a mod, dropping its oldest record when full
+    pub fn push(&self, mod_name: &str, entry: ModLogEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut mods = self.mods.lock().unwrap();
+        let entries = mods.entry(mod_name.to_string()).or_default();
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Get the records of a mod, from the oldest to the newest
+    pub fn records(&self, mod_name: &str) -> Vec<ModLogEntry> {
+        self.records_at_least(mod_name, LogLevel::Trace)
+    }
+
+    /// Get the records of a mod at or above a level, from the oldest to the newest
+    pub fn records_at_least(&self, mod_name: &str, level: LogLevel) -> Vec<ModLogEntry> {
+        self.mods
+            .lock()
+            .unwrap()
+            .get(mod_name)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter(|entry| entry.level >= level)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get the names of the mods having records
+    pub fn mods(&self) -> Vec<String> {
+        self.mods.lock().unwrap().keys().cloned().collect()
+    }
 
+    /// Remove the records of a mod
+    pub fn clear(&self, mod_name: &str) {
+        self.mods.lock().unwrap().remove(mod_name);
+    }
+}
+
+/// Handle log
+pub fn host_handle_log(mut caller: wasmtime::Caller<'_, ModState>, ptr: i32, len: i32) {
+    let memory = match caller.get_export("memory") {
+        Some(export) => match export.into_memory() {
+            Some(memory) => memory,
+            None => {
+                error!("get memory faild while logging");
+                return;
+            }
+        },
+        None => {
+            error!("get memory faild while logging");
+            return;
+        }
+    };
+
+    let mem_data = memory.data(&caller);
+    let Some(bytes) = memory_range(mem_data, ptr, len) else {
+        error!("log record out of bounds");
+        return;
+    };
+    let record: LogRecord = match caller.data().format().decode(bytes) {
+        Ok(record) => record,
+        Err(e) => {
+            error!("deserialize log record faild: {}", e);
+            return;
+        }
+    };
+
+    let state = caller.data_mut();
+    if record.level < state.log_level() {
+        return;
+    }
+    let mut record = record;
+    if let Some(limiter) = state.log_limiter_mut() {
+        match limiter.check(&record, Instant::now()) {
+            None => return,
+            Some(0) => {}
+            Some(repeated) => record
+                .fields
+                .push(("repeated".to_string(), repeated.to_string())),
+        }
+    }
+
+    let mod_name = state.get_mod_name().unwrap_or_default();
+    let system = state.current_system().unwrap_or_default();
+    emit_log_record(mod_name, system, &record);
+
+    // Keep the record for in-game consoles
+    let Some(world) = state.get_world() else {
+        return;
+    };
+    // Only shared access is needed, so this also works while running in parallel
+    let world_ref = unsafe { world.world() };
+    if let Some(buffer) = world_ref.get_resource::<ModLogBuffer>() {
+        let timestamp = world_ref
+            .get_resource::<Time<Real>>()
+            .map(|time| time.elapsed())
+            .unwrap_or_default();
+        buffer.push(
+            mod_name,
+            ModLogEntry {
+                level: record.level,
+                timestamp,
+                system: state.current_system().map(|system| system.to_string()),
+                message: record.message,
+                fields: record.fields,
+            },
+        );
+    }
+}
+
+/// Emit a log record of a mod through `bevy::log`
+pub fn emit_log_record(mod_name: &str, system: &str, record: &LogRecord) {
+    if record.fields.is_empty() {
+        log_event!(
+            record.level,
+            mod_name = %mod_name,
+            system = %system,
+            "{}",
+            record.message
+        );
+    } else {
+        let fields = format_fields(&record.fields);
+        log_event!(
+            record.level,
+            mod_name = %mod_name,
+            system = %system,
+            fields = %fields,
+            "{}",
+            record.message
+        );
+    }
+}
+
+/// Format structured fields as `key=value key=value`
+pub fn format_fields(fields: &[(String, String)]) -> String {
+    fields
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(message: &str) -> LogRecord {
+        LogRecord {
+            level: LogLevel::Info,
+            message: message.to_string(),
+            fields: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn repeated_records_are_collapsed() {
+        let mut limiter = LogRateLimiter::new(Duration::from_secs(1));
+        let start = Instant::now();
+        assert_eq!(limiter.check(&record("hit"), start), Some(0));
+        assert_eq!(limiter.check(&record("hit"), start), None);
+        assert_eq!(limiter.check(&record("hit"), start + Duration::from_millis(500)), None);
+        // Other records are not affected
+        assert_eq!(limiter.check(&record("miss"), start), Some(0));
+        // The next one after the interval reports the suppressed repeats
+        assert_eq!(limiter.check(&record("hit"), start + Duration::from_secs(1)), Some(2));
+        assert_eq!(limiter.check(&record("hit"), start + Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn tracked_records_are_bounded() {
+        let mut limiter = LogRateLimiter::new(Duration::from_secs(60));
+        let now = Instant::now();
+        for i in 0..MAX_TRACKED_RECORDS + 10 {
+            assert_eq!(limiter.check(&record(&i.to_string()), now), Some(0));
+        }
+        assert!(limiter.seen.len() <= MAX_TRACKED_RECORDS);
+    }
+}
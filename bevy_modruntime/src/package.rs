@@ -0,0 +1,208 @@
+//! Packaged mods
+//!
+//! A mod package is a zip archive with the `.wmod` extension, holding a `mod.toml`
+//! manifest, the wasm module and an assets folder. The assets of a package are exposed
+//! through the `mod://` asset source, as `mod://<mod name>/<path>`.
+
+use anyhow::anyhow;
+use bevy_modtypes::LogLevel;
+use bevy::asset::io::memory::{Dir, MemoryAssetReader};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Id of the asset source exposing the assets of mod packages
+pub const MOD_ASSET_SOURCE: &str = "mod";
+
+/// File extension of mod packages
+pub const PACKAGE_EXTENSION: &str = "wmod";
+
+/// Name of the manifest in a mod package
+pub const MANIFEST_FILE: &str = "mod.toml";
+
+/// Manifest of a mod package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModManifest {
+    /// Name of the mod, also the root of its assets in the `mod://` source
+    pub name: String,
+    /// Version of the mod
+    #[serde(default)]
+    pub version: String,
+    /// Description of the mod
+    #[serde(default)]
+    pub description: String,
+    /// Path of the wasm module in the package
+    #[serde(default = "default_module")]
+    pub module: String,
+    /// Path of the assets folder in the package
+    #[serde(default = "default_assets")]
+    pub assets: String,
+    /// Minimum level of the logs of the mod, like `"warn"`
+    #[serde(default)]
+    pub log_level: Option<LogLevel>,
+}
+
+fn default_module() -> String {
+    "mod.wasm".to_string()
+}
+
+fn default_assets() -> String {
+    "assets".to_string()
+}
+
+/// Content of a mod package
+pub struct ModPackage {
+    /// Manifest of the mod
+    pub manifest: ModManifest,
+    /// Bytes of the wasm module
+    pub module: Vec<u8>,
+    /// Assets, by path relative to the assets folder
+    pub assets: Vec<(PathBuf, Vec<u8>)>,
+}
+
+impl ModPackage {
+    /// Read a mod package from the bytes of its zip archive
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+
+        // Read the manifest
+        let manifest: ModManifest = {
+            let mut file = archive
+                .by_name(MANIFEST_FILE)
+                .map_err(|e| anyhow!("Missing '{}' in mod package: {}", MANIFEST_FILE, e))?;
+            let mut text = String::new();
+            file.read_to_string(&mut text)?;
+            toml::from_str(&text)?
+        };
+
+        // Read the module
+        let module = {
+            let mut file = archive.by_name(&manifest.module).map_err(|e| {
+                anyhow!("Missing module '{}' in mod package: {}", manifest.module, e)
+            })?;
+            let mut module = Vec::new();
+            file.read_to_end(&mut module)?;
+            module
+        };
+
+        // Read the assets
+        let assets_dir = Path::new(&manifest.assets);
+        let mut assets = Vec::new();
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            if file.is_dir() {
+                continue;
+            }
+            // Skip entries escaping the archive, like `../file`
+            let Some(path) = file.enclosed_name() else {
+                warn!("Skipping invalid path '{}' in mod package", file.name());
+                continue;
+            };
+            let Ok(relative) = path.strip_prefix(assets_dir) else {
+                continue;
+            };
+            let relative = relative.to_path_buf();
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            assets.push((relative, data));
+        }
+
+        Ok(Self {
+            manifest,
+            module,
+            assets,
+        })
+    }
+
+    /// Read a mod package from a file
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+/// Whether a path is a mod package, by its extension
+pub fn is_package_path(path: impl AsRef<Path>) -> bool {
+    path.as_ref()
+        .extension()
+        .is_some_and(|extension| extension == PACKAGE_EXTENSION)
+}
+
+/// Resource with the assets of all mounted mod packages
+///
+/// It backs the `mod://` asset source, in which every mod has its own folder.
+#[derive(Resource, Clone, Default)]
+pub struct ModPackages {
+    /// In-memory folder with the assets of all mods
+    root: Dir,
+    /// Mounted asset paths, by mod name
+    files: Arc<Mutex<HashMap<String, Vec<PathBuf>>>>,
+    /// Mods loaded from a package
+    packages: Arc<Mutex<HashSet<String>>>,
+}
+
+impl ModPackages {
+    /// Create an asset reader over the mounted assets
+    pub fn reader(&self) -> MemoryAssetReader {
+        MemoryAssetReader {
+            root: self.root.clone(),
+        }
+    }
+
+    /// Mount the assets of a package, replacing the assets previously mounted for the mod
+    pub fn mount(&self, package: &ModPackage) {
+        let mod_name = &package.manifest.name;
+        self.unmount(mod_name);
+
+        let mod_dir = Path::new(mod_name);
+        let mut files = Vec::with_capacity(package.assets.len());
+        for (path, data) in &package.assets {
+            let path = mod_dir.join(path);
+            self.root.insert_asset(&path, data.clone());
+            files.push(path);
+        }
+        info!("Mounted {} assets of mod '{}'", files.len(), mod_name);
+
+        self.files.lock().unwrap().insert(mod_name.clone(), files);
+        self.packages.lock().unwrap().insert(mod_name.clone());
+    }
+
+    /// Whether the assets of a mod come from its package
+    pub fn is_package(&self, mod_name: &str) -> bool {
+        self.packages.lock().unwrap().contains(mod_name)
+    }
+
+    /// Mount a single asset of a mod, replacing the asset previously mounted at the path
+    pub fn insert_asset(&self, mod_name: &str, path: impl AsRef<Path>, data: Vec<u8>) {
+        let path = Path::new(mod_name).join(path);
+        self.root.insert_asset(&path, data);
+
+        let mut files = self.files.lock().unwrap();
+        let mod_files = files.entry(mod_name.to_string()).or_default();
+        if !mod_files.contains(&path) {
+            mod_files.push(path);
+        }
+    }
+
+    /// Remove the mounted assets of a mod
+    pub fn unmount(&self, mod_name: &str) {
+        self.packages.lock().unwrap().remove(mod_name);
+        if let Some(files) = self.files.lock().unwrap().remove(mod_name) {
+            for path in files {
+                self.root.remove_asset(&path);
+            }
+        }
+    }
+
+    /// Get the mounted asset paths of a mod, relative to the `mod://` source
+    pub fn files(&self, mod_name: &str) -> Vec<PathBuf> {
+        self.files
+            .lock()
+            .unwrap()
+            .get(mod_name)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,305 @@
+//! System parameters for mods
+//!
+//! This module provides the host-side implementation of mod system parameters.
+//! All parameters of a system are fetched in one call before the system runs,
+//! and the mutations made by the system are applied after it returns.
+
+use crate::ModState;
+use crate::asset::ModAssetHandles;
+use crate::component::ModComponent;
+use crate::dynamic::{ModDefinedComponents, insert_defined_resource};
+use crate::query::query_entities_from_world;
+use crate::resource::{find_resource_registration, query_resource_from_world};
+use crate::spawn::spawn_entity_with_components;
+use crate::utils::{memory_range, write_host_result};
+use bevy::ecs::world::unsafe_world_cell::UnsafeWorldCell;
+use bevy::prelude::*;
+use bevy_modtypes::{Format, HostError, SchemaKind, SystemParamData, SystemParamRequest, SystemWrites};
+
+/// Handle system params fetch from WASM
+pub fn host_handle_fetch_system_params(
+    mut caller: wasmtime::Caller<'_, ModState>,
+    requests_ptr: i32,
+    requests_len: i32,
+    result_ptr: i32,
+) -> i32 {
+    // Read requests from WASM memory
+    let memory = match caller.get_export("memory").and_then(|export| export.into_memory()) {
+        Some(memory) => memory,
+        None => {
+            error!("get memory faild while fetching system params");
+            return 0;
+        }
+    };
+
+    let mem_data = memory.data(&caller);
+    let Some(requests_bytes) = memory_range(mem_data, requests_ptr, requests_len) else {
+        error!("system param requests out of bounds");
+        return 0;
+    };
+
+    // Deserialize requests
+    let format = caller.data().format();
+    let requests: Vec<SystemParamRequest> = match format.decode(requests_bytes) {
+        Ok(requests) => requests,
+        Err(e) => {
+            error!("deserialize system param requests faild: {}", e);
+            return 0;
+        }
+    };
+
+    // Get the Bevy world from the caller's data
+    let world = match caller.data().get_world() {
+        Some(world) => world,
+        None => {
+            error!("get bevy world faild while fetching system params");
+            return 0;
+        }
+    };
+
+    let mut data = fetch_system_params(&world, &requests, format);
+
+    // Do not send the data whose schema differs from the host
+    let state = caller.data();
+    for (request, data) in requests.iter().zip(data.iter_mut()) {
+        match (request, data) {
+            (SystemParamRequest::Query { components }, SystemParamData::Query(rows))
+                if components
+                    .iter()
+                    .any(|id| state.has_schema_mismatch(SchemaKind::Component, id)) =>
+            {
+                rows.clear();
+            }
+            (SystemParamRequest::Resource { id }, SystemParamData::Resource(resource))
+                if state.has_schema_mismatch(SchemaKind::Resource, id) =>
+            {
+                *resource = None;
+            }
+            _ => {}
+        }
+    }
+    let serialized_data = match format.encode(&data) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("serialize system params faild: {}", e);
+            return 0;
+        }
+    };
+
+    write_host_result(&mut caller, &memory, &serialized_data, result_ptr)
+}
+
+/// Fetch the data of each system param from the Bevy world, serialized in the given format
+pub fn fetch_system_params(
+    world: &UnsafeWorldCell<'_>,
+    requests: &[SystemParamRequest],
+    format: Format,
+) -> Vec<SystemParamData> {
+    requests
+        .iter()
+        .map(|request| match request {
+            SystemParamRequest::Query { components } => {
+                let entities = query_entities_from_world(world, components, format).unwrap_or_default();
+                SystemParamData::Query(
+                    entities
+                        .into_iter()
+                        .map(|(entity, components)| (entity.to_bits(), components))
+                        .collect(),
+                )
+            }
+            SystemParamRequest::Resource { id } => {
+                SystemParamData::Resource(query_resource_from_world(world, id, format))
+            }
+        })
+        .collect()
+}
+
+/// Handle system writes from WASM
+///
+/// Returns 0 on success, or the code of the first [`HostError`], the other writes are
+/// still applied. Writes deferred while running in parallel are checked when applied,
+/// their failures are only logged.
+pub fn host_handle_apply_system_writes(
+    mut caller: wasmtime::Caller<'_, ModState>,
+    writes_ptr: i32,
+    writes_len: i32,
+) -> u32 {
+    HostError::code(apply_mod_system_writes(&mut caller, writes_ptr, writes_len))
+}
+
+/// Apply the writes sent by a mod
+fn apply_mod_system_writes(
+    caller: &mut wasmtime::Caller<'_, ModState>,
+    writes_ptr: i32,
+    writes_len: i32,
+) -> Result<(), HostError> {
+    // Read writes from WASM memory
+    let memory = match caller.get_export("memory") {
+        Some(export) => export.into_memory().ok_or(HostError::Unavailable)?,
+        None => {
+            error!("Failed to get memory while applying system writes");
+            return Err(HostError::Unavailable);
+        }
+    };
+
+    let mem_data = memory.data(&caller);
+    let Some(writes_bytes) = memory_range(mem_data, writes_ptr, writes_len) else {
+        error!("System writes out of bounds");
+        return Err(HostError::InvalidData);
+    };
+
+    // Deserialize writes
+    let format = caller.data().format();
+    let mut writes: SystemWrites = match format.decode(writes_bytes) {
+        Ok(writes) => writes,
+        Err(e) => {
+            error!("Failed to deserialize system writes: {}", e);
+            return Err(HostError::InvalidData);
+        }
+    };
+
+    // Skip the writes whose schema differs from the host
+    let state = caller.data();
+    let count = writes_count(&writes);
+    for (_, components) in &mut writes.components {
+        components.retain(|(id, _)| !state.has_schema_mismatch(SchemaKind::Component, id));
+    }
+    writes
+        .resources
+        .retain(|(id, _)| !state.has_schema_mismatch(SchemaKind::Resource, id));
+    let result = if writes_count(&writes) == count {
+        Ok(())
+    } else {
+        Err(HostError::SchemaMismatch)
+    };
+
+    // Get the Bevy world from the caller's data
+    let world = match caller.data().get_world() {
+        Some(world) => world,
+        None => {
+            error!("Failed to get Bevy world while applying system writes");
+            return Err(HostError::Unavailable);
+        }
+    };
+
+    // Defer the writes while running in parallel with other mods
+    if caller.data().is_deferred() {
+        caller
+            .data_mut()
+            .push_deferred_write(DeferredWrite::System(writes, format));
+        return result;
+    }
+
+    result.and(apply_system_writes(&world, writes, format))
+}
+
+/// Number of components and resources written
+fn writes_count(writes: &SystemWrites) -> usize {
+    let components: usize = writes
+        .components
+        .iter()
+        .map(|(_, components)| components.len())
+        .sum();
+    components + writes.resources.len()
+}
+
+/// A world write made by a mod while running in parallel with other mods
+///
+/// It is applied once all mods running in parallel are finished.
+pub(crate) enum DeferredWrite {
+    /// Mutations made through system params, in the format of the mod
+    System(SystemWrites, Format),
+    /// Entity spawn as `[(component id, data)]`, in the format of the mod
+    Spawn(Vec<(String, Vec<u8>)>, Format),
+    /// Asset loaded for a mod, kept alive by id
+    Asset {
+        mod_name: String,
+        id: String,
+        handle: UntypedHandle,
+    },
+}
+
+/// Apply a deferred world write
+pub(crate) fn apply_deferred_write(world: &UnsafeWorldCell<'_>, write: DeferredWrite) {
+    match write {
+        DeferredWrite::System(writes, format) => {
+            if let Err(e) = apply_system_writes(world, writes, format) {
+                error!("Failed to apply deferred system writes: {}", e);
+            }
+        }
+        DeferredWrite::Spawn(components, format) => {
+            if let Err(e) = spawn_entity_with_components(world, &components, format) {
+                error!("Failed to apply deferred spawn: {}", e);
+            }
+        }
+        DeferredWrite::Asset {
+            mod_name,
+            id,
+            handle,
+        } => {
+            let world_mut = unsafe { world.world_mut() };
+            world_mut
+                .resource_mut::<ModAssetHandles>()
+                .insert(&mod_name, id, handle);
+        }
+    }
+}
+
+/// Apply the mutations made by a mod system to the Bevy world
+///
+/// Returns the first error, the other mutations are still applied.
+pub fn apply_system_writes(
+    world: &UnsafeWorldCell<'_>,
+    writes: SystemWrites,
+    format: Format,
+) -> Result<(), HostError> {
+    let world_mut = unsafe { world.world_mut() };
+    let type_registry = world_mut.resource::<AppTypeRegistry>().clone();
+    let registry = type_registry.read();
+    // Inserting components does not change the components defined by mods
+    let defined = unsafe { world.get_resource::<ModDefinedComponents>() };
+    let mut result = Ok(());
+
+    for (entity_bits, components) in writes.components {
+        let Ok(entity) = Entity::try_from_bits(entity_bits) else {
+            error!("Invalid entity bits from mod: {}", entity_bits);
+            result = result.and(Err(HostError::NoEntity));
+            continue;
+        };
+        let Ok(mut entity_mut) = world_mut.get_entity_mut(entity) else {
+            warn!("Entity {:?} no longer exists, skipping write", entity);
+            result = result.and(Err(HostError::NoEntity));
+            continue;
+        };
+
+        for (component_id, component_data) in components {
+            let Some(component) = ModComponent::find(&component_id, &registry, defined) else {
+                error!("Component registration not found for ID: {}", component_id);
+                result = result.and(Err(HostError::UnknownId));
+                continue;
+            };
+            result = result.and(component.insert(&mut entity_mut, &component_data, format));
+        }
+    }
+
+    for (resource_id, resource_data) in writes.resources {
+        let Some(registration) = find_resource_registration(&resource_id) else {
+            result = result.and(insert_defined_resource(
+                world_mut,
+                &resource_id,
+                resource_data,
+                format,
+            ));
+            continue;
+        };
+        match (registration.deserialize_fn)(&resource_data, format) {
+            Ok(resource) => result = result.and((registration.insert_fn)(world_mut, resource)),
+            Err(e) => {
+                error!("Failed to deserialize resource {}: {}", resource_id, e);
+                result = result.and(Err(HostError::InvalidData));
+            }
+        }
+    }
+
+    result
+}
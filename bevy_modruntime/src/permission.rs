@@ -0,0 +1,39 @@
+//! Mod permissions
+//!
+//! Mods can only reach the host through what they are granted here. The permissions are
+//! set per mod on the `WasmModPlugin`, the manifest of a package cannot grant anything.
+
+use std::path::{Component, Path};
+
+/// Host access granted to a mod
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModPermissions {
+    /// Prefixes of the asset paths and ids the mod can read
+    read_assets: Vec<String>,
+}
+
+impl ModPermissions {
+    /// Allow the mod to read the assets whose path or id starts with the prefix
+    ///
+    /// A prefix like `config/` grants a folder of the host, `other_mod:` the assets of
+    /// another mod, and an empty prefix every asset.
+    pub fn allow_read_assets(mut self, prefix: impl Into<String>) -> Self {
+        self.read_assets.push(prefix.into());
+        self
+    }
+
+    /// Whether the mod can read an asset, by path or id
+    ///
+    /// Paths leaving their folder with `..` are never allowed.
+    pub fn can_read_asset(&self, id: &str) -> bool {
+        if Path::new(id)
+            .components()
+            .any(|component| component == Component::ParentDir)
+        {
+            return false;
+        }
+        self.read_assets
+            .iter()
+            .any(|prefix| id.starts_with(prefix.as_str()))
+    }
+}
@@ -1,7 +1,10 @@
-use bevy::{ecs::world::unsafe_world_cell::UnsafeWorldCell, log::*};
+use bevy::{ecs::world::unsafe_world_cell::UnsafeWorldCell, log::*, prelude::Entity};
 use crate::ModState;
-use bevy_modtypes::HostModResult;
-use crate::component::find_component_registration;
+use bevy_modtypes::{Format, HostModResult, SchemaKind};
+use crate::component::ModComponent;
+use crate::dynamic::ModDefinedComponents;
+use crate::utils::memory_range;
+use bevy::ecs::reflect::AppTypeRegistry;
 
 /// Handle component query from WASM
 pub fn host_handle_query_components(
@@ -23,21 +26,32 @@ pub fn host_handle_query_components(
     };
 
     let mem_data = memory.data(&caller);
-    let component_ids_bytes =
-        &mem_data[component_ids_ptr as usize..(component_ids_ptr + component_ids_len) as usize];
+    let Some(component_ids_bytes) = memory_range(mem_data, component_ids_ptr, component_ids_len)
+    else {
+        error!("component ids out of bounds while querying components");
+        return 0;
+    };
 
     // Deserialize component IDs
-    let component_ids: Vec<String> =
-        match bincode::serde::decode_from_slice(component_ids_bytes, bincode::config::standard()) {
-            Ok((ids, _)) => ids,
-            Err(e) => {
-                error!(
-                    "deserialize component id faild while querying components {}: {}",
-                    component_ids_ptr, e
-                );
-                return 0;
-            }
-        };
+    let format = caller.data().format();
+    let component_ids: Vec<String> = match format.decode(component_ids_bytes) {
+        Ok(ids) => ids,
+        Err(e) => {
+            error!(
+                "deserialize component id faild while querying components {}: {}",
+                component_ids_ptr, e
+            );
+            return 0;
+        }
+    };
+
+    // The components are not exchanged if their schema differs from the host
+    if component_ids
+        .iter()
+        .any(|id| caller.data().has_schema_mismatch(SchemaKind::Component, id))
+    {
+        return 0;
+    }
 
     // Get the Bevy world from the caller's data
     let world = match caller.data().get_world() {
@@ -52,7 +66,7 @@ pub fn host_handle_query_components(
     };
 
     // Query components from the world
-    let serialized_data = match query_components_from_world(&world, &component_ids) {
+    let serialized_data = match query_components_from_world(&world, &component_ids, format) {
         Some(data) => data,
         None => {
             warn!(
@@ -103,68 +117,69 @@ pub fn host_handle_query_components(
     }
 }
 
-/// Query components from the Bevy world
+/// Query components from the Bevy world, serialized in the given format
 pub fn query_components_from_world(
     world: &UnsafeWorldCell<'_>,
     component_ids: &[String],
+    format: Format,
 ) -> Option<Vec<u8>> {
+    let entities = query_entities_from_world(world, component_ids, format)?;
+
+    // Drop the entity ids, only the components are returned
+    let serialized_entities: Vec<Vec<Vec<u8>>> = entities
+        .into_iter()
+        .map(|(_, components)| components)
+        .collect();
+
+    // Serialize the vector of serialized entities
+    format.encode(&serialized_entities).ok()
+}
+
+/// Query entities having all the given components from the Bevy world
+///
+/// Returns each matched entity with its serialized components, in the order of `component_ids`.
+pub fn query_entities_from_world(
+    world: &UnsafeWorldCell<'_>,
+    component_ids: &[String],
+    format: Format,
+) -> Option<Vec<(Entity, Vec<Vec<u8>>)>> {
     if component_ids.is_empty() {
         return None;
     }
 
-    // Find all component registrations
-    let mut registrations = Vec::new();
-    let mut type_ids = Vec::new();
+    // Find all components
+    let mut components = Vec::new();
     let mut component_db_ids = Vec::new();
 
     unsafe {
-        let world_origin = world.world_mut();
+        let world_origin = world.world();
+        let type_registry = world_origin.get_resource::<AppTypeRegistry>()?.clone();
+        let registry = type_registry.read();
+        let defined = world_origin.get_resource::<ModDefinedComponents>();
 
-        // Get the component registrations and their IDs
+        // Get the components and their IDs
         for component_id in component_ids {
-            let registration = find_component_registration(component_id)?;
-            let type_id = (registration.get_type_id)();
-            let component_db_id = world_origin.components().get_id(type_id)?;
+            let component = ModComponent::find(component_id, &registry, defined)?;
+            let component_db_id = component.component_id(world_origin.components())?;
 
-            registrations.push(registration);
-            type_ids.push(type_id);
+            components.push(component);
             component_db_ids.push(component_db_id);
         }
 
-        // Get components
-        let mut entity_results: Vec<Vec<bevy::ptr::Ptr<'_>>> = Vec::new();
-
         // Iterate through all entities in the world
-        for entity_ref in world_origin.iter_entities() {
-            let entity = entity_ref.id();
-            let mut entity_components = Vec::new();
-
-            'a: for &component_id in &component_db_ids {
-                if let Some(component_ptr) = world_origin.get_by_id(entity, component_id) {
-                    entity_components.push(component_ptr);
-                } else {
-                    continue 'a;
-                }
-            }
-            entity_results.push(entity_components);
-        }
-
-        // Serialize the components
-        let mut serialized_entities = Vec::new();
-        for entity_components in entity_results {
+        let mut results = Vec::new();
+        'entities: for entity_ref in world_origin.iter_entities() {
             let mut serialized_components = Vec::new();
-            for (i, component_ptr) in entity_components.iter().enumerate() {
-                let serialized_component = (registrations[i].serialize_fn)(*component_ptr);
-                serialized_components.push(serialized_component);
+            for (i, &component_id) in component_db_ids.iter().enumerate() {
+                // Skip entities missing any of the components
+                let Ok(component_ptr) = entity_ref.get_by_id(component_id) else {
+                    continue 'entities;
+                };
+                serialized_components.push(components[i].serialize(component_ptr, format));
             }
-            serialized_entities.push(serialized_components);
+            results.push((entity_ref.id(), serialized_components));
         }
 
-        // Serialize the vector of serialized entities
-        let serialized_data =
-            bincode::serde::encode_to_vec(&serialized_entities, bincode::config::standard())
-                .ok()?;
-
-        Some(serialized_data)
+        Some(results)
     }
 }
@@ -2,13 +2,19 @@
 //!
 //! This module provides functionality for registering and managing resources
 //! that can be accessed by mods.
+//!
+//! Resources defined by mods are found by id too, see [`crate::dynamic`].
 
 use crate::ModState;
 use bevy::ecs::world::unsafe_world_cell::UnsafeWorldCell;
 use bevy::prelude::*;
 use bevy::reflect::TypeRegistry;
+use crate::component::{DeserializeFn, DuplicateId, SerializeFn, find_duplicate_ids};
+use crate::dynamic::query_defined_resource;
+use crate::utils::memory_range;
 use std::any::{Any, TypeId};
 pub use bevy_modtypes::HostModResult;
+use bevy_modtypes::{Format, HostError, SchemaKind};
 
 // Resource registry using linkme
 #[linkme::distributed_slice]
@@ -18,14 +24,20 @@ pub static RESOURCE_REGISTRY: [ResourceRegistration] = [..];
 pub struct ResourceRegistration {
     /// The ID of the resource
     pub id: &'static str,
+    /// Hash of the shape of the type, checked against mods, `0` to skip the check
+    pub schema_hash: u64,
     /// Serialization function
-    pub serialize_fn: fn(bevy::ptr::Ptr<'_>) -> Vec<u8>,
-    /// Deserialization function
-    pub deserialize_fn: fn(&[u8]) -> Box<dyn Any>,
+    pub serialize_fn: SerializeFn,
+    /// Deserialization function, failing on malformed data
+    pub deserialize_fn: DeserializeFn,
     /// Type id
     pub get_type_id: fn() -> TypeId,
+    /// Rust type name, for messages
+    pub type_name: fn() -> &'static str,
     /// Reg type function
     pub reg_fn: fn(&mut TypeRegistry),
+    /// Insert resource function, failing for read-only resources
+    pub insert_fn: fn(&mut World, Box<dyn Any>) -> Result<(), HostError>,
 }
 
 /// Find a resource registration by ID
@@ -33,6 +45,17 @@ pub fn find_resource_registration(id: &str) -> Option<&'static ResourceRegistrat
     RESOURCE_REGISTRY.iter().find(|reg| reg.id == id)
 }
 
+/// Find the resource ids registered by more than one type
+///
+/// Lookups return the first registration of an id, the others are never reached.
+pub fn duplicate_resource_ids() -> Vec<DuplicateId> {
+    find_duplicate_ids(
+        RESOURCE_REGISTRY
+            .iter()
+            .map(|registration| (registration.id, (registration.type_name)())),
+    )
+}
+
 /// Handle resource query from WASM
 pub fn host_handle_query_resources(
     mut caller: wasmtime::Caller<'_, ModState>,
@@ -53,21 +76,31 @@ pub fn host_handle_query_resources(
     };
 
     let mem_data = memory.data(&caller);
-    let resource_id_bytes =
-        &mem_data[resource_id_ptr as usize..(resource_id_ptr + resource_id_len) as usize];
+    let Some(resource_id_bytes) = memory_range(mem_data, resource_id_ptr, resource_id_len) else {
+        error!("resource id out of bounds while querying resource");
+        return 0;
+    };
 
     // Deserialize resource ID
-    let resource_id: String =
-        match bincode::serde::decode_from_slice(resource_id_bytes, bincode::config::standard()) {
-            Ok((id, _)) => id,
-            Err(e) => {
-                error!(
-                    "deserialize resource id faild while querying resource {}: {}",
-                    resource_id_ptr, e
-                );
-                return 0;
-            }
-        };
+    let format = caller.data().format();
+    let resource_id: String = match format.decode(resource_id_bytes) {
+        Ok(id) => id,
+        Err(e) => {
+            error!(
+                "deserialize resource id faild while querying resource {}: {}",
+                resource_id_ptr, e
+            );
+            return 0;
+        }
+    };
+
+    // The resource is not exchanged if its schema differs from the host
+    if caller
+        .data()
+        .has_schema_mismatch(SchemaKind::Resource, &resource_id)
+    {
+        return 0;
+    }
 
     // Get the Bevy world from the caller's data
     let world = match caller.data().get_world() {
@@ -82,7 +115,7 @@ pub fn host_handle_query_resources(
     };
 
     // Query resource from the world
-    let serialized_data = match query_resource_from_world(&world, &resource_id) {
+    let serialized_data = match query_resource_from_world(&world, &resource_id, format) {
         Some(data) => {
             data
         },
@@ -135,16 +168,19 @@ pub fn host_handle_query_resources(
     }
 }
 
-/// Query a resource from the Bevy world
+/// Query a resource from the Bevy world, serialized in the given format
 pub fn query_resource_from_world(
     world: &UnsafeWorldCell<'_>,
     resource_id: &str,
+    format: Format,
 ) -> Option<Vec<u8>> {
-    // Find the resource registration
-    let registration = find_resource_registration(resource_id)?;
+    // Find the resource registration, or the resource defined by a mod
+    let Some(registration) = find_resource_registration(resource_id) else {
+        return query_defined_resource(world, resource_id, format);
+    };
     
     unsafe {
-        let world_origin = world.world_mut();
+        let world_origin = world.world();
         
         // Get the resource type id
         let type_id = (registration.get_type_id)();
@@ -164,7 +200,7 @@ pub fn query_resource_from_world(
         match world_origin.get_resource_by_id(component_id) {
             Some(resource_ptr) => {
                 // Serialize the resource
-                let serialized_resource = (registration.serialize_fn)(resource_ptr);
+                let serialized_resource = (registration.serialize_fn)(resource_ptr, format);
                 Some(serialized_resource)
             },
             None => {
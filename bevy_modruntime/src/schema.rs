@@ -0,0 +1,95 @@
+//! Schema checks
+//!
+//! `#[mod_component]`, `#[mod_resource]` and the SDK macros hash the shape of the types
+//! they mark. When a mod first uses a component or resource id, it sends the hash of its
+//! type, which the host compares with the registration. A mismatch is reported as an error
+//! of the mod, and the host stops exchanging the data of that type with the mod instead of
+//! failing to decode it.
+//!
+//! The hash is shallow, it covers the names of the field types but not their own fields, so
+//! a nested type changing its layout is not detected.
+//!
+//! Components and resources defined by mods are checked the same way, against the
+//! defining mod.
+
+use crate::ModState;
+use crate::asset::read_string_from_memory;
+use crate::component::find_component_registration;
+use crate::dynamic::{ModDefinedComponents, ModDefinedResources};
+use crate::resource::find_resource_registration;
+use anyhow::anyhow;
+use bevy::prelude::*;
+use bevy_modtypes::SchemaKind;
+use wasmtime::{Caller, Result};
+
+/// Get the schema hash of a registered component or resource
+///
+/// Returns `None` if the id is not registered or its type is not checked, like
+/// reflected components and built-in registrations.
+pub fn registered_schema_hash(kind: SchemaKind, id: &str) -> Option<u64> {
+    let hash = match kind {
+        SchemaKind::Component => find_component_registration(id)?.schema_hash,
+        SchemaKind::Resource => find_resource_registration(id)?.schema_hash,
+    };
+    (hash != 0).then_some(hash)
+}
+
+/// Get the schema hash of a component or resource defined by a mod, with the name of the mod
+///
+/// Returns `None` if no mod defines the id or its type is not checked.
+fn defined_schema_hash(state: &ModState, kind: SchemaKind, id: &str) -> Option<(u64, String)> {
+    let world = state.get_world()?;
+    let definition = match kind {
+        SchemaKind::Component => unsafe { world.get_resource::<ModDefinedComponents>() }?.get(id)?,
+        SchemaKind::Resource => unsafe { world.get_resource::<ModDefinedResources>() }?.get(id)?,
+    };
+    (definition.schema_hash != 0).then(|| (definition.schema_hash, definition.mod_name.clone()))
+}
+
+/// Handle the schema check of a component or resource from WASM
+///
+/// Returns 1 if the mod can use the type, 0 if its schema differs from the host.
+pub fn host_handle_check_schema(
+    mut caller: Caller<'_, ModState>,
+    kind: u32,
+    id_ptr: i32,
+    id_len: i32,
+    hash: u64,
+) -> Result<u32> {
+    let Some(kind) = SchemaKind::from_index(kind) else {
+        return Err(anyhow!("Invalid schema kind {}", kind));
+    };
+    let memory = match caller.get_export("memory") {
+        Some(export) => match export.into_memory() {
+            Some(memory) => memory,
+            None => return Err(anyhow!("Failed to get memory export")),
+        },
+        None => return Err(anyhow!("Missing memory export")),
+    };
+    let id = read_string_from_memory(&memory, &mut caller, id_ptr as u32, id_len as u32)?;
+
+    // Types unknown to the host and the mods, or without a hash, are not checked
+    let (expected_hash, owner) = match registered_schema_hash(kind, &id) {
+        Some(host_hash) => (host_hash, "the host".to_string()),
+        None => match defined_schema_hash(caller.data(), kind, &id) {
+            Some((mod_hash, mod_name)) => (mod_hash, format!("mod '{}'", mod_name)),
+            None => return Ok(1),
+        },
+    };
+    if hash == 0 || hash == expected_hash {
+        return Ok(1);
+    }
+
+    let state = caller.data_mut();
+    error!(
+        "Schema of {} '{}' in mod '{}' differs from {}, update the SDK of the mod. \
+         The {} will not be exchanged with the mod.",
+        kind,
+        id,
+        state.get_mod_name().unwrap_or("unknown"),
+        owner,
+        kind,
+    );
+    state.add_schema_mismatch(kind, id);
+    Ok(0)
+}
@@ -0,0 +1,388 @@
+//! Mod SDK generation
+//!
+//! Mods access the components and resources of the host through their own copies of the
+//! types, which must have the same ids and field layouts. [`SdkGenerator`] writes these
+//! copies as a Rust crate from `COMPONENT_REGISTRY`, `RESOURCE_REGISTRY` and the reflected
+//! type information of the registered types, so the SDK never drifts from the host.
+//!
+//! Built-in registrations, with ids starting with `bevy::`, are skipped, their mod types
+//! come with `bevy_modapi`. Components then resources are emitted in the order of their
+//! ids, so the generated crate does not change between builds of the host.
+
+use crate::{COMPONENT_REGISTRY, RESOURCE_REGISTRY};
+use anyhow::{anyhow, bail};
+use bevy::reflect::{Reflect, Type, TypeInfo, TypeRegistry, VariantInfo};
+use std::any::TypeId;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Prefix of the ids of the built-in registrations
+const BUILTIN_PREFIX: &str = "bevy::";
+
+/// Crates whose types are taken from `bevy_math` in the SDK
+const MATH_CRATES: &[&str] = &["glam", "bevy_math"];
+
+/// Primitive types, kept as is in the SDK
+const PRIMITIVES: &[&str] = &[
+    "bool", "char", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64",
+    "i128", "isize", "f32", "f64",
+];
+
+/// Fields of a registered type ignored by reflection but serialized, like `Variant.0`
+///
+/// Recorded by `#[mod_component]` and `#[mod_resource]` as a custom reflection attribute.
+/// The SDK cannot define these fields, so generating it fails.
+#[derive(Reflect, Debug, Clone)]
+pub struct IgnoredFields(pub Vec<String>);
+
+/// Kind of a registered type
+#[derive(Clone, Copy)]
+enum Registered {
+    Component(&'static str),
+    Resource(&'static str),
+}
+
+/// Generator of the mod SDK crate of the host
+///
+/// ```ignore
+/// SdkGenerator::new("game_sdk").write_crate("game_sdk")?;
+/// ```
+pub struct SdkGenerator {
+    /// Name of the generated crate
+    name: String,
+    /// Version of the generated crate
+    version: String,
+    /// Dependency on `bevy_modsdk` in the generated manifest
+    modsdk_dependency: String,
+    /// Registry with the registered types and their fields
+    registry: TypeRegistry,
+}
+
+impl SdkGenerator {
+    /// Create a generator for a crate, with the components and resources of the host
+    pub fn new(name: impl Into<String>) -> Self {
+        let mut registry = TypeRegistry::new();
+        for registration in COMPONENT_REGISTRY {
+            (registration.reg_fn)(&mut registry);
+        }
+        for registration in RESOURCE_REGISTRY {
+            (registration.reg_fn)(&mut registry);
+        }
+
+        Self {
+            name: name.into(),
+            version: "0.1.0".to_string(),
+            modsdk_dependency: format!("\"{}\"", env!("CARGO_PKG_VERSION")),
+            registry,
+        }
+    }
+
+    /// Set the version of the generated crate
+    pub fn set_version(mut self, version: impl Into<String>) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Set the dependency on `bevy_modsdk`, like `{ path = "../bevy_modsdk" }`
+    pub fn set_modsdk_dependency(mut self, dependency: impl Into<String>) -> Self {
+        self.modsdk_dependency = dependency.into();
+        self
+    }
+
+    /// Generate the `Cargo.toml` of the crate
+    pub fn generate_manifest(&self) -> String {
+        format!(
+            "[package]\n\
+             name = \"{}\"\n\
+             version = \"{}\"\n\
+             edition = \"2024\"\n\
+             \n\
+             [dependencies]\n\
+             bevy_math = {{ version = \"0.16\", features = [\"serialize\"] }}\n\
+             bevy_modsdk = {}\n\
+             serde = {{ version = \"1.0\", features = [\"derive\"] }}\n",
+            self.name, self.version, self.modsdk_dependency
+        )
+    }
+
+    /// Generate the `lib.rs` of the crate
+    pub fn generate_lib(&self) -> anyhow::Result<String> {
+        let mut writer = SdkWriter {
+            registry: &self.registry,
+            names: HashMap::new(),
+            pending: VecDeque::new(),
+        };
+
+        // The order of the registries depends on the linker, sort each of them by id
+        let mut components: Vec<_> = COMPONENT_REGISTRY
+            .iter()
+            .map(|r| (r.id, (r.get_type_id)(), Registered::Component(r.id)))
+            .collect();
+        components.sort_by_key(|(id, ..)| *id);
+        let mut resources: Vec<_> = RESOURCE_REGISTRY
+            .iter()
+            .map(|r| (r.id, (r.get_type_id)(), Registered::Resource(r.id)))
+            .collect();
+        resources.sort_by_key(|(id, ..)| *id);
+
+        // Name the registered types first, so fields refer to them instead of copies
+        let mut registered = Vec::new();
+        for (id, type_id, kind) in components.into_iter().chain(resources) {
+            if id.starts_with(BUILTIN_PREFIX) {
+                continue;
+            }
+            let info = self
+                .registry
+                .get_type_info(type_id)
+                .ok_or_else(|| anyhow!("Type of '{}' is not reflected", id))?;
+            if let Some(IgnoredFields(fields)) = ignored_fields(info) {
+                bail!(
+                    "Fields {} of '{}' are ignored by reflection, add `#[serde(skip)]` to them or \
+                     stop ignoring them",
+                    fields.join(", "),
+                    id
+                );
+            }
+            writer.name_type(info)?;
+            registered.push((info, kind));
+        }
+
+        let mut lib = String::from(
+            "//! Components and resources of the host\n\
+             //!\n\
+             //! Generated by `bevy_modruntime::SdkGenerator`, do not edit.\n",
+        );
+
+        // Import only the used macros, so the crate builds without warnings
+        let has_components = registered
+            .iter()
+            .any(|(_, kind)| matches!(kind, Registered::Component(_)));
+        let has_resources = registered
+            .iter()
+            .any(|(_, kind)| matches!(kind, Registered::Resource(_)));
+        match (has_components, has_resources) {
+            (true, true) => lib.push_str("\nuse bevy_modsdk::{component, resource};\n"),
+            (true, false) => lib.push_str("\nuse bevy_modsdk::component;\n"),
+            (false, true) => lib.push_str("\nuse bevy_modsdk::resource;\n"),
+            (false, false) => {}
+        }
+        for (info, kind) in registered {
+            let attribute = match kind {
+                Registered::Component(id) => format!("#[component(id = \"{}\")]", id),
+                Registered::Resource(id) => format!("#[resource(id = \"{}\")]", id),
+            };
+            lib.push('\n');
+            lib.push_str(&writer.define(info, &attribute)?);
+        }
+
+        // Define the types used by the fields
+        while let Some(info) = writer.pending.pop_front() {
+            lib.push('\n');
+            lib.push_str(&writer.define(
+                info,
+                "#[derive(serde::Serialize, serde::Deserialize)]",
+            )?);
+        }
+
+        Ok(lib)
+    }
+
+    /// Write `src/lib.rs` of the crate, keeping its manifest
+    pub fn write_lib(&self, dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let src = dir.as_ref().join("src");
+        std::fs::create_dir_all(&src)?;
+        std::fs::write(src.join("lib.rs"), self.generate_lib()?)?;
+        Ok(())
+    }
+
+    /// Write the whole crate, with its manifest and `src/lib.rs`
+    pub fn write_crate(&self, dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let dir = dir.as_ref();
+        self.write_lib(dir)?;
+        std::fs::write(dir.join("Cargo.toml"), self.generate_manifest())?;
+        Ok(())
+    }
+}
+
+/// Get the fields of a registered type ignored by reflection but serialized
+fn ignored_fields(info: &TypeInfo) -> Option<&IgnoredFields> {
+    match info {
+        TypeInfo::Struct(info) => info.get_attribute(),
+        TypeInfo::TupleStruct(info) => info.get_attribute(),
+        TypeInfo::Enum(info) => info.get_attribute(),
+        _ => None,
+    }
+}
+
+/// Writer of the type definitions of the SDK
+struct SdkWriter<'a> {
+    /// Registry with the type information
+    registry: &'a TypeRegistry,
+    /// Names of the defined types
+    names: HashMap<TypeId, String>,
+    /// Types used by fields and not defined yet
+    pending: VecDeque<&'static TypeInfo>,
+}
+
+impl SdkWriter<'_> {
+    /// Give a name to a type defined in the SDK
+    fn name_type(&mut self, info: &'static TypeInfo) -> anyhow::Result<String> {
+        let ty = info.ty();
+        if let Some(name) = self.names.get(&ty.id()) {
+            return Ok(name.clone());
+        }
+
+        let name = ty
+            .ident()
+            .ok_or_else(|| anyhow!("Type '{}' has no name", ty.path()))?
+            .to_string();
+        if !info.generics().is_empty() {
+            bail!("Generic type '{}' is not supported in the SDK", ty.path());
+        }
+        if self.names.values().any(|other| *other == name) {
+            bail!("Two types of the SDK are named '{}', like '{}'", name, ty.path());
+        }
+        self.names.insert(ty.id(), name.clone());
+        Ok(name)
+    }
+
+    /// Get the type info of a field type
+    fn type_info(&self, ty: &Type) -> anyhow::Result<&'static TypeInfo> {
+        self.registry
+            .get_type_info(ty.id())
+            .ok_or_else(|| anyhow!("Type '{}' is not reflected", ty.path()))
+    }
+
+    /// Get the name of a field type in the SDK, queuing its definition if needed
+    fn field_type(&mut self, ty: &Type) -> anyhow::Result<String> {
+        let path = ty.path();
+        if PRIMITIVES.contains(&path) {
+            return Ok(path.to_string());
+        }
+        if path == "alloc::string::String" {
+            return Ok("String".to_string());
+        }
+        if ty
+            .crate_name()
+            .is_some_and(|name| MATH_CRATES.contains(&name))
+        {
+            return Ok(format!("bevy_math::{}", ty.short_path()));
+        }
+
+        let info = self.type_info(ty)?;
+        match info {
+            TypeInfo::List(list) => Ok(format!("Vec<{}>", self.field_type(&list.item_ty())?)),
+            TypeInfo::Array(array) => Ok(format!(
+                "[{}; {}]",
+                self.field_type(&array.item_ty())?,
+                array.capacity()
+            )),
+            TypeInfo::Map(map) => Ok(format!(
+                "std::collections::HashMap<{}, {}>",
+                self.field_type(&map.key_ty())?,
+                self.field_type(&map.value_ty())?
+            )),
+            TypeInfo::Set(set) => Ok(format!(
+                "std::collections::HashSet<{}>",
+                self.field_type(&set.value_ty())?
+            )),
+            TypeInfo::Tuple(tuple) => {
+                let fields = tuple
+                    .iter()
+                    .map(|field| self.field_type(field.ty()))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                match fields.as_slice() {
+                    [field] => Ok(format!("({},)", field)),
+                    _ => Ok(format!("({})", fields.join(", "))),
+                }
+            }
+            TypeInfo::Enum(info)
+                if ty.module_path() == Some("core::option") && ty.ident() == Some("Option") =>
+            {
+                let some = info
+                    .variant("Some")
+                    .and_then(|variant| variant.as_tuple_variant().ok())
+                    .and_then(|variant| variant.field_at(0))
+                    .ok_or_else(|| anyhow!("Invalid option type '{}'", path))?;
+                Ok(format!("Option<{}>", self.field_type(some.ty())?))
+            }
+            TypeInfo::Struct(_) | TypeInfo::TupleStruct(_) | TypeInfo::Enum(_) => {
+                let defined = self.names.contains_key(&ty.id());
+                let name = self.name_type(info)?;
+                if !defined {
+                    self.pending.push_back(info);
+                }
+                Ok(name)
+            }
+            TypeInfo::Opaque(_) => bail!("Opaque type '{}' is not supported in the SDK", path),
+        }
+    }
+
+    /// Get the fields of a tuple, like `(pub f32, pub u32)`
+    fn tuple_fields<'f>(
+        &mut self,
+        fields: impl Iterator<Item = &'f bevy::reflect::UnnamedField>,
+        visibility: &str,
+    ) -> anyhow::Result<String> {
+        let fields = fields
+            .map(|field| Ok(format!("{}{}", visibility, self.field_type(field.ty())?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(fields.join(", "))
+    }
+
+    /// Get the fields of a struct, one per line
+    fn named_fields<'f>(
+        &mut self,
+        fields: impl Iterator<Item = &'f bevy::reflect::NamedField>,
+        indent: &str,
+        visibility: &str,
+    ) -> anyhow::Result<String> {
+        let mut text = String::new();
+        for field in fields {
+            let ty = self.field_type(field.ty())?;
+            writeln!(text, "{}{}{}: {},", indent, visibility, field.name(), ty)?;
+        }
+        Ok(text)
+    }
+
+    /// Define a type with the attribute above it
+    fn define(&mut self, info: &'static TypeInfo, attribute: &str) -> anyhow::Result<String> {
+        let name = self.name_type(info)?;
+        let mut text = format!("{}\n", attribute);
+        match info {
+            TypeInfo::Struct(info) if info.field_len() == 0 => {
+                writeln!(text, "pub struct {};", name)?;
+            }
+            TypeInfo::Struct(info) => {
+                let fields = self.named_fields(info.iter(), "    ", "pub ")?;
+                write!(text, "pub struct {} {{\n{}}}\n", name, fields)?;
+            }
+            TypeInfo::TupleStruct(info) => {
+                let fields = self.tuple_fields(info.iter(), "pub ")?;
+                writeln!(text, "pub struct {}({});", name, fields)?;
+            }
+            TypeInfo::Enum(info) => {
+                writeln!(text, "pub enum {} {{", name)?;
+                for variant in info.iter() {
+                    match variant {
+                        VariantInfo::Unit(variant) => {
+                            writeln!(text, "    {},", variant.name())?;
+                        }
+                        VariantInfo::Tuple(variant) => {
+                            let fields = self.tuple_fields(variant.iter(), "")?;
+                            writeln!(text, "    {}({}),", variant.name(), fields)?;
+                        }
+                        VariantInfo::Struct(variant) => {
+                            let fields = self.named_fields(variant.iter(), "        ", "")?;
+                            write!(text, "    {} {{\n{}    }},\n", variant.name(), fields)?;
+                        }
+                    }
+                }
+                text.push_str("}\n");
+            }
+            _ => bail!("Type '{}' cannot be defined in the SDK", info.ty().path()),
+        }
+        Ok(text)
+    }
+}
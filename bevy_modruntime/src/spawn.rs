@@ -3,83 +3,128 @@
 //! This module provides the host-side implementation for spawning entities from mods.
 
 use crate::ModState;
+use crate::param::DeferredWrite;
 use bevy::ecs::world::unsafe_world_cell::UnsafeWorldCell;
 use bevy::prelude::*;
-use std::any::Any;
+use crate::component::ModComponent;
+use crate::dynamic::ModDefinedComponents;
+use crate::utils::memory_range;
+use bevy_modtypes::{Format, HostError, SchemaKind};
 
 /// Handle entity spawn request from WASM
+///
+/// Returns 0 on success, or the code of a [`HostError`]. Spawns deferred while running in
+/// parallel are checked when applied, their failures are only logged.
 pub fn host_handle_spawn_entities(
     mut caller: wasmtime::Caller<'_, ModState>,
     components_ptr: i32,
     components_len: i32,
-) {
+) -> u32 {
+    HostError::code(spawn_entities(&mut caller, components_ptr, components_len))
+}
+
+/// Spawn the entity requested by a mod
+fn spawn_entities(
+    caller: &mut wasmtime::Caller<'_, ModState>,
+    components_ptr: i32,
+    components_len: i32,
+) -> Result<(), HostError> {
     // Read components data from WASM memory
     let memory = match caller.get_export("memory") {
-        Some(export) => export.into_memory().unwrap(),
+        Some(export) => export.into_memory().ok_or(HostError::Unavailable)?,
         None => {
             error!("Failed to get memory while spawning entities");
-            return;
+            return Err(HostError::Unavailable);
         }
     };
 
     let mem_data = memory.data(&caller);
-    let components_bytes =
-        &mem_data[components_ptr as usize..(components_ptr + components_len) as usize];
+    let Some(components_bytes) = memory_range(mem_data, components_ptr, components_len) else {
+        error!("Spawned components out of bounds");
+        return Err(HostError::InvalidData);
+    };
 
     // Deserialize components data
-    let components_data: Vec<(String, Vec<u8>)> =
-        match bincode::serde::decode_from_slice(components_bytes, bincode::config::standard()) {
-            Ok((data, _)) => data,
-            Err(e) => {
-                error!(
-                    "Failed to deserialize components data while spawning entities: {}",
-                    e
-                );
-                return;
-            }
-        };
+    let format = caller.data().format();
+    let components_data: Vec<(String, Vec<u8>)> = match format.decode(components_bytes) {
+        Ok(data) => data,
+        Err(e) => {
+            error!(
+                "Failed to deserialize components data while spawning entities: {}",
+                e
+            );
+            return Err(HostError::InvalidData);
+        }
+    };
+
+    // Refuse the components whose schema differs from the host
+    if components_data.iter().any(|(id, _)| {
+        caller
+            .data()
+            .has_schema_mismatch(SchemaKind::Component, id)
+    }) {
+        return Err(HostError::SchemaMismatch);
+    }
 
     // Get the Bevy world from the caller's data
     let world = match caller.data().get_world() {
         Some(world) => world,
         None => {
             error!("Failed to get Bevy world while spawning entities");
-            return;
+            return Err(HostError::Unavailable);
         }
     };
 
+    // Defer the spawn while running in parallel with other mods
+    if caller.data().is_deferred() {
+        caller
+            .data_mut()
+            .push_deferred_write(DeferredWrite::Spawn(components_data, format));
+        return Ok(());
+    }
+
     // Spawn the entity with components
-    spawn_entity_with_components(&world, &components_data);
+    spawn_entity_with_components(&world, &components_data, format)
 }
 
 /// Spawn an entity with the specified components
-fn spawn_entity_with_components(
+///
+/// Nothing is spawned if a component is unknown or its data is malformed, the components
+/// are decoded before spawning the entity.
+pub(crate) fn spawn_entity_with_components(
     world: &UnsafeWorldCell<'_>,
-    components_data: &Vec<(String, Vec<u8>)>,
-) {
+    components_data: &[(String, Vec<u8>)],
+    format: Format,
+) -> Result<(), HostError> {
     unsafe {
         let world_mut = world.world_mut();
-        let mut entity_commands = world_mut.spawn_empty();
+        let type_registry = world_mut.resource::<AppTypeRegistry>().clone();
+        let registry = type_registry.read();
+        // Inserting components does not change the components defined by mods
+        let defined = world.get_resource::<ModDefinedComponents>();
 
+        // Decode all the components first, so nothing is spawned for malformed data
+        let mut components = Vec::with_capacity(components_data.len());
         for (component_id, component_data) in components_data {
-            // Find the component registration
-            let registration = match crate::component::find_component_registration(component_id) {
-                Some(reg) => reg,
-                None => {
-                    error!("Component registration not found for ID: {}", component_id);
-                    continue;
-                }
+            let Some(component) = ModComponent::find(component_id, &registry, defined) else {
+                error!("Component registration not found for ID: {}", component_id);
+                return Err(HostError::UnknownId);
             };
+            components.push(component.decode(component_data, format)?);
+        }
 
-            // Deserialize the component
-            let component_any: Box<dyn Any> = (registration.deserialize_fn)(component_data);
-
-            // Use the insert function to add the component to the entity
-            (registration.insert_fn)(&mut entity_commands, component_any);
+        let mut entity_commands = world_mut.spawn_empty();
+        for component in components {
+            // Only read-only components and missing entities fail once decoded
+            if let Err(e) = component.insert(&mut entity_commands) {
+                entity_commands.despawn();
+                return Err(e);
+            }
         }
 
         // Actually spawn the entity
         let entity = entity_commands.id();
         info!("Spawned entity with ID: {:?}", entity);
+        Ok(())
     }
 }
@@ -0,0 +1,162 @@
+//! Mod storage
+//!
+//! A key-value store per mod, kept by the host in the [`ModStorage`] resource. Values stay
+//! when the mod is reloaded or unloaded, and tools can inspect them. Each mod only reaches
+//! its own values.
+
+use crate::ModState;
+use crate::asset::read_string_from_memory;
+use crate::dynamic::ModData;
+use crate::utils::{memory_range, write_host_result};
+use anyhow::anyhow;
+use bevy::prelude::*;
+use bevy_modtypes::HostError;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use wasmtime::{Caller, Memory, Result};
+
+/// Values stored by mods, by mod name and key
+///
+/// Mods running in parallel write it through a shared reference, hence the lock.
+#[derive(Resource, Debug, Default)]
+pub struct ModStorage(Mutex<HashMap<String, HashMap<String, ModData>>>);
+
+impl ModStorage {
+    /// Get a value stored by a mod
+    pub fn get(&self, mod_name: &str, key: &str) -> Option<ModData> {
+        self.0.lock().unwrap().get(mod_name)?.get(key).cloned()
+    }
+
+    /// Store a value for a mod, replacing the previous one
+    pub fn set(&self, mod_name: &str, key: impl Into<String>, value: ModData) {
+        self.0
+            .lock()
+            .unwrap()
+            .entry(mod_name.to_string())
+            .or_default()
+            .insert(key.into(), value);
+    }
+
+    /// Remove a value stored by a mod, returning it
+    pub fn remove(&self, mod_name: &str, key: &str) -> Option<ModData> {
+        self.0.lock().unwrap().get_mut(mod_name)?.remove(key)
+    }
+
+    /// Get the keys of the values stored by a mod
+    pub fn keys(&self, mod_name: &str) -> Vec<String> {
+        self.0
+            .lock()
+            .unwrap()
+            .get(mod_name)
+            .map(|values| values.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Get the names of the mods with stored values
+    pub fn mods(&self) -> Vec<String> {
+        self.0.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Remove all the values stored by a mod
+    pub fn clear(&self, mod_name: &str) {
+        self.0.lock().unwrap().remove(mod_name);
+    }
+}
+
+/// Get the memory of the calling mod
+fn caller_memory(caller: &mut Caller<'_, ModState>) -> Result<Memory> {
+    match caller.get_export("memory") {
+        Some(export) => export
+            .into_memory()
+            .ok_or_else(|| anyhow!("Failed to get memory export")),
+        None => Err(anyhow!("Missing memory export")),
+    }
+}
+
+/// Get the storage and the name of the calling mod
+fn caller_storage<'w>(caller: &Caller<'_, ModState>) -> Option<(&'w ModStorage, String)> {
+    let Some(world) = caller.data().get_world() else {
+        error!("get bevy world faild while accessing mod storage");
+        return None;
+    };
+    let Some(mod_name) = caller.data().get_mod_name() else {
+        error!("Unknown calling mod while accessing mod storage");
+        return None;
+    };
+    // Only shared access is needed, so this also works while running in parallel
+    let storage = unsafe { world.world() }.get_resource::<ModStorage>()?;
+    Some((storage, mod_name.to_string()))
+}
+
+/// Handle reading a value of the mod storage from WASM
+///
+/// Returns the length of the value written to `result_ptr`, 0 if there is none.
+pub fn host_handle_storage_get(
+    mut caller: Caller<'_, ModState>,
+    key_ptr: i32,
+    key_len: i32,
+    result_ptr: i32,
+) -> Result<i32> {
+    let memory = caller_memory(&mut caller)?;
+    let key = read_string_from_memory(&memory, &mut caller, key_ptr as u32, key_len as u32)?;
+    let Some((storage, mod_name)) = caller_storage(&caller) else {
+        return Ok(0);
+    };
+    let Some(value) = storage.get(&mod_name, &key) else {
+        return Ok(0);
+    };
+
+    // Values stored in another format cannot be decoded by the mod
+    let format = caller.data().format();
+    if value.format != format {
+        error!(
+            "Value '{}' of mod '{}' is stored in {}, it cannot be read in {}",
+            key, mod_name, value.format, format
+        );
+        return Ok(0);
+    }
+    Ok(write_host_result(&mut caller, &memory, &value.data, result_ptr))
+}
+
+/// Handle storing a value in the mod storage from WASM
+///
+/// Returns 0 on success, or the code of a `HostError`.
+pub fn host_handle_storage_set(
+    mut caller: Caller<'_, ModState>,
+    key_ptr: i32,
+    key_len: i32,
+    value_ptr: i32,
+    value_len: i32,
+) -> Result<u32> {
+    let memory = caller_memory(&mut caller)?;
+    let key = read_string_from_memory(&memory, &mut caller, key_ptr as u32, key_len as u32)?;
+    let Some(data) =
+        memory_range(memory.data(&caller), value_ptr, value_len).map(|data| data.to_vec())
+    else {
+        error!("Value '{}' out of bounds while accessing mod storage", key);
+        return Ok(HostError::code(Err(HostError::InvalidData)));
+    };
+    let Some((storage, mod_name)) = caller_storage(&caller) else {
+        return Ok(HostError::code(Err(HostError::Unavailable)));
+    };
+
+    let format = caller.data().format();
+    storage.set(&mod_name, key, ModData { format, data });
+    Ok(HostError::code(Ok(())))
+}
+
+/// Handle removing a value from the mod storage from WASM
+///
+/// Returns 1 if the value existed, 0 otherwise.
+pub fn host_handle_storage_remove(
+    mut caller: Caller<'_, ModState>,
+    key_ptr: i32,
+    key_len: i32,
+) -> Result<u32> {
+    let memory = caller_memory(&mut caller)?;
+    let key = read_string_from_memory(&memory, &mut caller, key_ptr as u32, key_len as u32)?;
+    let Some((storage, mod_name)) = caller_storage(&caller) else {
+        return Ok(0);
+    };
+    Ok(storage.remove(&mod_name, &key).is_some() as u32)
+}
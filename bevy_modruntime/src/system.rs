@@ -1,11 +1,21 @@
-use crate::LoadedMods;
+use crate::param::apply_deferred_write;
+use crate::loading::ModLoadingState;
+use crate::{LoadedMods, ModState, WasmModPlugin};
+use crate::diagnostic::{mod_diagnostic_path, system_diagnostic_path, system_fuel_diagnostic_path};
+use crate::utils::memory_range;
+use bevy::diagnostic::Diagnostics;
+use bevy::ecs::system::SystemState;
 use bevy::prelude::*;
+use bevy::tasks::{ComputeTaskPool, TaskPool};
+use bevy_modtypes::SystemAccess;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
 use wasmtime::TypedFunc;
 
 /// Schedule of mod system
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum ModSystemSchedule {
-    /// In PostStartup
+    /// Once, after the mod finished loading
     Startup,
     /// In PostUpdate
     Update,
@@ -23,60 +33,427 @@ impl From<u8> for ModSystemSchedule {
 /// Mod system info
 pub struct ModSystemInfo {
     pub mod_name: String,
+    pub system_name: String,
     pub schedule: ModSystemSchedule,
     pub run_func: TypedFunc<(), ()>,
+    /// Access declared by the system, `None` if it may access the whole world
+    pub access: Option<SystemAccess>,
 }
 
 /// Resource to store mod systems info
 #[derive(Resource)]
 pub struct ModSystems(pub Vec<ModSystemInfo>);
 
+/// Resource to store which mods and mod systems are disabled
+///
+/// States are keyed by mod and system name, so they are kept when a mod is reloaded.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct ModSystemStates {
+    /// Disabled mods
+    disabled_mods: HashSet<String>,
+    /// Disabled systems as `(mod name, system name)`
+    disabled_systems: HashSet<(String, String)>,
+}
+
+impl ModSystemStates {
+    /// Enable or disable a system of a mod
+    pub fn set_system_enabled(&mut self, mod_name: &str, system_name: &str, enabled: bool) {
+        let key = (mod_name.to_string(), system_name.to_string());
+        if enabled {
+            self.disabled_systems.remove(&key);
+        } else {
+            self.disabled_systems.insert(key);
+        }
+    }
+
+    /// Enable or disable all systems of a mod
+    pub fn set_mod_enabled(&mut self, mod_name: &str, enabled: bool) {
+        if enabled {
+            self.disabled_mods.remove(mod_name);
+        } else {
+            self.disabled_mods.insert(mod_name.to_string());
+        }
+    }
+
+    /// Whether a mod is enabled
+    pub fn is_mod_enabled(&self, mod_name: &str) -> bool {
+        !self.disabled_mods.contains(mod_name)
+    }
+
+    /// Whether a system runs, i.e. both the system and its mod are enabled
+    pub fn is_system_enabled(&self, mod_name: &str, system_name: &str) -> bool {
+        self.is_mod_enabled(mod_name)
+            && !self
+                .disabled_systems
+                .contains(&(mod_name.to_string(), system_name.to_string()))
+    }
+
+    /// Iterate over the disabled mods
+    pub fn disabled_mods(&self) -> impl Iterator<Item = &str> {
+        self.disabled_mods.iter().map(String::as_str)
+    }
+
+    /// Iterate over the disabled systems as `(mod name, system name)`
+    pub fn disabled_systems(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.disabled_systems
+            .iter()
+            .map(|(mod_name, system_name)| (mod_name.as_str(), system_name.as_str()))
+    }
+}
+
+/// A request from a mod to enable or disable a system or a whole mod
+pub(crate) struct SystemStateRequest {
+    /// Target mod name
+    pub mod_name: String,
+    /// Target system name, `None` for the whole mod
+    pub system_name: Option<String>,
+    /// Whether to enable the target
+    pub enabled: bool,
+}
+
+impl ModSystemStates {
+    /// Apply a request made by a mod
+    pub(crate) fn apply_request(&mut self, request: SystemStateRequest) {
+        match request.system_name {
+            Some(system_name) => {
+                self.set_system_enabled(&request.mod_name, &system_name, request.enabled)
+            }
+            None => self.set_mod_enabled(&request.mod_name, request.enabled),
+        }
+    }
+}
+
 /// System to execute startup mod systems
+///
+/// Startup systems of a mod run once, in the first frame after the mod finished loading.
+/// They always run one after another.
 pub fn execute_mod_startup_systems(
-    mut mod_systems: ResMut<ModSystems>,
-    r_loaded_mods: Res<LoadedMods>,
+    world: &mut World,
+    diagnostics: &mut SystemState<Diagnostics>,
+) {
+    let loaded = world.resource_mut::<ModLoadingState>().take_startup_pending();
+    if loaded.is_empty() {
+        return;
+    }
+
+    run_mod_systems(
+        world,
+        diagnostics,
+        ModSystemSchedule::Startup,
+        Some(&loaded),
+        false,
+    );
+}
+
+/// System to execute update mod systems
+///
+/// Mods whose systems declare disjoint access run in parallel, unless disabled on the plugin.
+pub fn execute_mod_update_systems(
+    world: &mut World,
+    diagnostics: &mut SystemState<Diagnostics>,
+) {
+    let parallel = world.resource::<WasmModPlugin>().parallel_execution;
+    run_mod_systems(
+        world,
+        diagnostics,
+        ModSystemSchedule::Update,
+        None,
+        parallel,
+    );
+}
+
+/// Enabled systems of one mod in a schedule
+struct ModSystemGroup<'a> {
+    /// Name of the mod
+    mod_name: &'a str,
+    /// Systems in run order, with their index in `ModSystems`
+    systems: Vec<(usize, &'a ModSystemInfo)>,
+    /// Union of the accesses of the systems, `None` if one of them accesses the whole world
+    access: Option<SystemAccess>,
+}
+
+/// Measurements of one mod system run
+struct SystemRun {
+    /// Index of the system in `ModSystems`
+    index: usize,
+    /// Wall time in milliseconds
+    elapsed_ms: f64,
+    /// Consumed fuel, if fuel metering is enabled
+    fuel: Option<u64>,
+}
+
+/// Execute all mod systems of a schedule, measuring each of them
+///
+/// The driver is exclusive, so mods are the only ones reaching the world while they run,
+/// through the world cell of their state. If `only_mods` is set, only the systems of these
+/// mods run.
+fn run_mod_systems(
+    world: &mut World,
+    diagnostics: &mut SystemState<Diagnostics>,
+    schedule: ModSystemSchedule,
+    only_mods: Option<&HashSet<String>>,
+    parallel: bool,
 ) {
-    // Execute each mod system
-    for mod_info in &mut mod_systems.0 {
-        if mod_info.schedule != ModSystemSchedule::Startup {
+    // Mods never access these resources, they are taken out of the world while mods run
+    let runs = world.resource_scope(|world, mod_systems: Mut<ModSystems>| {
+        world.resource_scope(|world, loaded_mods: Mut<LoadedMods>| {
+            run_mod_system_batches(world, &mod_systems, &loaded_mods, schedule, only_mods, parallel)
+        })
+    });
+
+    // Publish measurements
+    let mut diagnostics_param = diagnostics.get_mut(world);
+    let mut mod_times: HashMap<String, f64> = HashMap::new();
+    for (mod_name, system_name, run) in runs {
+        diagnostics_param.add_measurement(
+            &system_diagnostic_path(&mod_name, &system_name),
+            || run.elapsed_ms,
+        );
+        if let Some(fuel) = run.fuel {
+            diagnostics_param.add_measurement(
+                &system_fuel_diagnostic_path(&mod_name, &system_name),
+                || fuel as f64,
+            );
+        }
+        *mod_times.entry(mod_name).or_default() += run.elapsed_ms;
+    }
+    for (mod_name, elapsed_ms) in mod_times {
+        diagnostics_param.add_measurement(&mod_diagnostic_path(&mod_name), || elapsed_ms);
+    }
+    diagnostics.apply(world);
+}
+
+/// Execute the mod systems of a schedule by batches, returning the runs with the mod and
+/// system names
+fn run_mod_system_batches(
+    world: &mut World,
+    mod_systems: &ModSystems,
+    loaded_mods: &LoadedMods,
+    schedule: ModSystemSchedule,
+    only_mods: Option<&HashSet<String>>,
+    parallel: bool,
+) -> Vec<(String, String, SystemRun)> {
+    // Group the enabled systems by mod, keeping the load order
+    let states = world.resource::<ModSystemStates>();
+    let mut groups: Vec<ModSystemGroup> = Vec::new();
+    for (index, mod_info) in mod_systems.0.iter().enumerate() {
+        if mod_info.schedule != schedule
+            || only_mods.is_some_and(|mods| !mods.contains(&mod_info.mod_name))
+            || !states.is_system_enabled(&mod_info.mod_name, &mod_info.system_name)
+        {
             continue;
         }
-        let store_arc = match r_loaded_mods.0.get(&mod_info.mod_name) {
-            Some(loaded_mod) => loaded_mod.store.clone(),
-            None => {
-                error!("executing system err: mod {} not found", &mod_info.mod_name);
+        let group = match groups.last_mut() {
+            Some(group) if group.mod_name == mod_info.mod_name => group,
+            _ => {
+                groups.push(ModSystemGroup {
+                    mod_name: &mod_info.mod_name,
+                    systems: Vec::new(),
+                    access: Some(SystemAccess::default()),
+                });
+                groups.last_mut().unwrap()
+            }
+        };
+        group.systems.push((index, mod_info));
+        group.access = match (group.access.take(), &mod_info.access) {
+            (Some(mut access), Some(system_access)) => {
+                access.extend(system_access);
+                Some(access)
+            }
+            _ => None,
+        };
+    }
+
+    let mut runs = Vec::new();
+    for batch in build_batches(groups, parallel) {
+        if batch.len() == 1 {
+            runs.extend(run_mod_system_group(&batch[0], &batch[0].systems, loaded_mods, false));
+        } else {
+            // Run the mods of the batch concurrently one system at a time, deferring their
+            // world writes. The writes of each round are applied before the next one, so the
+            // later systems of a mod see the writes of its earlier ones, as when run alone.
+            let task_pool = ComputeTaskPool::get_or_init(TaskPool::default);
+            let rounds = batch.iter().map(|group| group.systems.len()).max().unwrap_or(0);
+            for round in 0..rounds {
+                runs.extend(
+                    task_pool
+                        .scope(|scope| {
+                            for group in &batch {
+                                let Some(system) = group.systems.get(round) else {
+                                    continue;
+                                };
+                                let systems = std::slice::from_ref(system);
+                                scope.spawn(async move {
+                                    run_mod_system_group(group, systems, loaded_mods, true)
+                                });
+                            }
+                        })
+                        .into_iter()
+                        .flatten(),
+                );
+                apply_deferred_writes(world, &batch, loaded_mods);
+            }
+        }
+
+        // Apply the state changes requested by the mods
+        for group in &batch {
+            let Some(loaded_mod) = loaded_mods.0.get(group.mod_name) else {
                 continue;
+            };
+            let requests = loaded_mod.store.write().unwrap().data_mut().take_system_state_requests();
+            let mut states = world.resource_mut::<ModSystemStates>();
+            for request in requests {
+                states.apply_request(request);
             }
+        }
+    }
+
+    runs.into_iter()
+        .map(|run| {
+            let info = &mod_systems.0[run.index];
+            (info.mod_name.clone(), info.system_name.clone(), run)
+        })
+        .collect()
+}
+
+/// Apply the world writes deferred by the mods of a batch
+fn apply_deferred_writes(world: &mut World, batch: &[ModSystemGroup], loaded_mods: &LoadedMods) {
+    let world = world.as_unsafe_world_cell();
+    for group in batch {
+        let Some(loaded_mod) = loaded_mods.0.get(group.mod_name) else {
+            continue;
         };
-        let mut store = store_arc.write().unwrap();
+        let writes = loaded_mod.store.write().unwrap().data_mut().take_deferred_writes();
+        for write in writes {
+            apply_deferred_write(&world, write);
+        }
+    }
+}
+
+/// Split mod groups into batches that can run in parallel
+///
+/// Batches keep the load order: a mod conflicting with the current batch starts a new one.
+/// Mods accessing the whole world always run alone.
+fn build_batches(groups: Vec<ModSystemGroup>, parallel: bool) -> Vec<Vec<ModSystemGroup>> {
+    let mut batches: Vec<Vec<ModSystemGroup>> = Vec::new();
+    for group in groups {
+        let fits_last = parallel
+            && batches.last().is_some_and(|batch| {
+                batch.iter().all(|other| match (&group.access, &other.access) {
+                    (Some(access), Some(other_access)) => access.is_compatible(other_access),
+                    _ => false,
+                })
+            });
+        if fits_last {
+            batches.last_mut().unwrap().push(group);
+        } else {
+            batches.push(vec![group]);
+        }
+    }
+    batches
+}
+
+/// Execute systems of one mod in order
+fn run_mod_system_group(
+    group: &ModSystemGroup,
+    systems: &[(usize, &ModSystemInfo)],
+    loaded_mods: &LoadedMods,
+    deferred: bool,
+) -> Vec<SystemRun> {
+    let store_arc = match loaded_mods.0.get(group.mod_name) {
+        Some(loaded_mod) => loaded_mod.store.clone(),
+        None => {
+            error!("executing system err: mod {} not found", group.mod_name);
+            return Vec::new();
+        }
+    };
+    let mut store = store_arc.write().unwrap();
+    store.data_mut().set_deferred(deferred);
+
+    let mut runs = Vec::with_capacity(systems.len());
+    for &(index, mod_info) in systems {
+        let _span = info_span!(
+            "mod_system",
+            mod_name = %mod_info.mod_name,
+            system = %mod_info.system_name
+        )
+        .entered();
+
+        store
+            .data_mut()
+            .set_current_system(Some(mod_info.system_name.clone()));
+        let fuel_before = store.get_fuel().ok();
+        let start = Instant::now();
         match mod_info.run_func.call(&mut *store, ()) {
             Ok(_) => {}
             Err(e) => error!("Failed to execute system: {}", e),
         }
+        let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+        store.data_mut().set_current_system(None);
+
+        let fuel = match (fuel_before, store.get_fuel()) {
+            (Some(before), Ok(after)) => Some(before.saturating_sub(after)),
+            _ => None,
+        };
+        runs.push(SystemRun {
+            index,
+            elapsed_ms,
+            fuel,
+        });
     }
+
+    store.data_mut().set_deferred(false);
+    runs
 }
 
-/// System to execute update mod systems
-pub fn execute_mod_update_systems(
-    mut mod_systems: ResMut<ModSystems>,
-    r_loaded_mods: Res<LoadedMods>,
+/// Handle system state change from WASM
+///
+/// An empty mod name targets the calling mod, and an empty system name targets the whole mod.
+pub fn host_handle_set_system_enabled(
+    mut caller: wasmtime::Caller<'_, ModState>,
+    mod_name_ptr: i32,
+    mod_name_len: i32,
+    system_name_ptr: i32,
+    system_name_len: i32,
+    enabled: i32,
 ) {
-    // Execute each mod system
-    for mod_info in &mut mod_systems.0 {
-        if mod_info.schedule != ModSystemSchedule::Update {
-            continue;
+    let memory = match caller.get_export("memory").and_then(|export| export.into_memory()) {
+        Some(memory) => memory,
+        None => {
+            error!("Failed to get memory while setting system state");
+            return;
         }
-        let store_arc = match r_loaded_mods.0.get(&mod_info.mod_name) {
-            Some(loaded_mod) => loaded_mod.store.clone(),
+    };
+
+    let mem_data = memory.data(&caller);
+    let read_str = |ptr: i32, len: i32| -> Option<String> {
+        let bytes = memory_range(mem_data, ptr, len)?;
+        String::from_utf8(bytes.to_vec()).ok()
+    };
+    let (Some(mod_name), Some(system_name)) = (
+        read_str(mod_name_ptr, mod_name_len),
+        read_str(system_name_ptr, system_name_len),
+    ) else {
+        error!("Invalid names while setting system state");
+        return;
+    };
+
+    // Default to the calling mod
+    let mod_name = if mod_name.is_empty() {
+        match caller.data().get_mod_name() {
+            Some(name) => name.to_string(),
             None => {
-                error!("executing system err: mod {} not found", &mod_info.mod_name);
-                continue;
+                error!("Unknown calling mod while setting system state");
+                return;
             }
-        };
-        let mut store = store_arc.write().unwrap();
-        match mod_info.run_func.call(&mut *store, ()) {
-            Ok(_) => {}
-            Err(e) => error!("Failed to execute system: {}", e),
         }
-    }
+    } else {
+        mod_name
+    };
+
+    caller.data_mut().push_system_state_request(SystemStateRequest {
+        mod_name,
+        system_name: (!system_name.is_empty()).then_some(system_name),
+        enabled: enabled != 0,
+    });
 }
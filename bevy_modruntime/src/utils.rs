@@ -1,6 +1,7 @@
 use anyhow::anyhow;
 use bevy::log::{info, warn};
-use bevy_modtypes::SystemInfo;
+use bevy_modtypes::{Format, LogLevel, SystemAccess, SystemInfo};
+use serde::de::DeserializeOwned;
 use std::mem;
 use wasmtime::{Instance, Result, Store, TypedFunc};
 
@@ -101,6 +102,101 @@ pub(crate) fn get_mod_system_info<T>(
     Ok(system_info)
 }
 
+/// Get the access declared by a system
+///
+/// Returns `None` if the system does not declare its access.
+pub(crate) fn get_mod_system_access<T>(
+    mut store: &mut Store<T>,
+    instance: &Instance,
+    system_name: &str,
+    format: Format,
+) -> Result<Option<SystemAccess>> {
+    // Only systems with parameters declare their access
+    let access_fn_name = format!("__mod_access_system_{}", system_name);
+    let Ok(access_func) = instance.get_typed_func::<(), i32>(&mut store, &access_fn_name) else {
+        return Ok(None);
+    };
+
+    // Call the function and get the pointer to the HostModResult
+    let result_ptr = access_func.call(&mut store, ())? as usize;
+
+    let memory = instance
+        .get_memory(&mut store, "memory")
+        .ok_or_else(|| anyhow!("missing memory export"))?;
+
+    // Read the HostModResult describing the serialized access
+    let mut result_bytes = [0u8; 8];
+    memory.read(&mut store, result_ptr, &mut result_bytes)?;
+    let data_ptr = u32::from_le_bytes(result_bytes[0..4].try_into()?) as usize;
+    let data_len = u32::from_le_bytes(result_bytes[4..8].try_into()?) as usize;
+
+    let mut data = vec![0u8; data_len];
+    memory.read(&mut store, data_ptr, &mut data)?;
+    let access = format.decode(&data)?;
+
+    Ok(Some(access))
+}
+
+/// Get the components or resources a mod defines, unknown to the host
+///
+/// `export` is `__mod_components` or `__mod_resources`, mods without it define none.
+pub(crate) fn get_mod_definitions<T, D: DeserializeOwned>(
+    mut store: &mut Store<T>,
+    instance: &Instance,
+    export: &str,
+    format: Format,
+) -> Result<Vec<D>> {
+    let Ok(definitions_func) = instance.get_typed_func::<(), i32>(&mut store, export) else {
+        return Ok(Vec::new());
+    };
+
+    // Call the function and get the pointer to the HostModResult
+    let result_ptr = definitions_func.call(&mut store, ())? as usize;
+
+    let memory = instance
+        .get_memory(&mut store, "memory")
+        .ok_or_else(|| anyhow!("missing memory export"))?;
+
+    // Read the HostModResult describing the serialized definitions
+    let mut result_bytes = [0u8; 8];
+    memory.read(&mut store, result_ptr, &mut result_bytes)?;
+    let data_ptr = u32::from_le_bytes(result_bytes[0..4].try_into()?) as usize;
+    let data_len = u32::from_le_bytes(result_bytes[4..8].try_into()?) as usize;
+
+    let mut data = vec![0u8; data_len];
+    memory.read(&mut store, data_ptr, &mut data)?;
+    Ok(format.decode(&data)?)
+}
+
+/// Set the minimum level of the logs a mod sends to the host
+pub(crate) fn set_mod_log_level<T>(
+    mut store: &mut Store<T>,
+    instance: &Instance,
+    level: LogLevel,
+) -> Result<()> {
+    let set_log_level: TypedFunc<u32, ()> =
+        instance.get_typed_func(&mut store, "__mod_set_log_level")?;
+    set_log_level.call(&mut store, level.index())
+}
+
+/// Agree with a mod on the format of the data it exchanges with the host
+///
+/// Mods built without format support use bincode.
+pub(crate) fn negotiate_mod_format<T>(
+    mut store: &mut Store<T>,
+    instance: &Instance,
+    preferred: Format,
+) -> Result<Format> {
+    let Ok(get_formats) = instance.get_typed_func::<(), u32>(&mut store, "__mod_formats") else {
+        return Ok(Format::Bincode);
+    };
+    let format = Format::negotiate(preferred, get_formats.call(&mut store, ())?);
+
+    let set_format: TypedFunc<u32, ()> = instance.get_typed_func(&mut store, "__mod_set_format")?;
+    set_format.call(&mut store, format.index())?;
+    Ok(format)
+}
+
 /// Get the name of a mod
 pub(crate) fn get_mod_name<T>(mut store: &mut Store<T>, instance: &Instance) -> Result<String> {
     let get_name_func: TypedFunc<(), i32> =
@@ -153,14 +249,15 @@ pub fn host_handle_free_memory(mut caller: wasmtime::Caller<'_, ModState>, ptr:
     // Check if the pointer matches our fixed allocation offset
     if ptr == 0x100000 {
         // Zero out the memory region that was used for the allocation
-        if let Some(export) = caller.get_export("memory") {
-            if let Some(memory) = export.into_memory() {
-                let zero_data = vec![0u8; len];
-                if let Err(e) = memory.write(&mut caller, ptr as usize, &zero_data) {
-                    warn!("Failed to zero out memory region: {}", e);
-                } else {
-                    info!("Memory region zeroed out: ptr={}, len={}", ptr, len);
-                }
+        if let Some(memory) = caller
+            .get_export("memory")
+            .and_then(|export| export.into_memory())
+        {
+            let zero_data = vec![0u8; len];
+            if let Err(e) = memory.write(&mut caller, ptr as usize, &zero_data) {
+                warn!("Failed to zero out memory region: {}", e);
+            } else {
+                info!("Memory region zeroed out: ptr={}, len={}", ptr, len);
             }
         }
     } else {
@@ -172,3 +269,57 @@ pub fn host_handle_free_memory(mut caller: wasmtime::Caller<'_, ModState>, ptr:
         );
     }
 }
+
+/// Get a range of the memory of a mod, from a pointer and a length sent by the mod
+///
+/// Returns `None` if the range is out of bounds or overflows.
+pub(crate) fn memory_range(mem_data: &[u8], ptr: i32, len: i32) -> Option<&[u8]> {
+    // Wasm pointers and lengths are unsigned 32-bit values
+    let start = ptr as u32 as usize;
+    let end = start.checked_add(len as u32 as usize)?;
+    mem_data.get(start..end)
+}
+
+/// Write data returned to a mod into WASM memory
+///
+/// The data is written at the fixed result offset and a `HostModResult` describing it
+/// is written at `result_ptr`. Returns the length of the data, or 0 on failure.
+pub(crate) fn write_host_result(
+    caller: &mut wasmtime::Caller<'_, ModState>,
+    memory: &wasmtime::Memory,
+    data: &[u8],
+    result_ptr: i32,
+) -> i32 {
+    if data.is_empty() {
+        return 0;
+    }
+
+    // We'll use a simple approach: allocate at a fixed offset in the memory
+    // In a real implementation, we would need a proper memory allocator
+    let data_ptr_offset = 0x100000; // Start at 1MB offset
+
+    // Write the serialized data to WASM memory
+    if let Err(e) = memory.write(&mut *caller, data_ptr_offset, data) {
+        bevy::log::error!("Failed to write serialized data to WASM memory: {}", e);
+        return 0;
+    }
+
+    let result = bevy_modtypes::HostModResult {
+        data_ptr: data_ptr_offset as u32,
+        data_len: data.len() as u32,
+    };
+
+    // Write the HostModResult struct to WASM memory at the provided result_ptr
+    let result_bytes = unsafe {
+        std::slice::from_raw_parts(
+            &result as *const bevy_modtypes::HostModResult as *const u8,
+            std::mem::size_of::<bevy_modtypes::HostModResult>(),
+        )
+    };
+    if let Err(e) = memory.write(&mut *caller, result_ptr as usize, result_bytes) {
+        bevy::log::error!("Failed to write HostModResult to WASM memory: {}", e);
+        return 0;
+    }
+
+    data.len() as i32
+}
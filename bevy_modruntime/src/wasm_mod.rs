@@ -0,0 +1,238 @@
+//! Mods as Bevy assets
+//!
+//! `.wasm` files and `.wmod` packages are loaded through the `AssetServer` as [`WasmMod`]
+//! assets, so mods can come from any asset source and are reloaded when the file watcher
+//! sees a change.
+
+use crate::engine::ModEngine;
+use crate::loading::{ModLoadStatus, ModLoadingEvent, ModLoadingState, ModLoadingTasks};
+use crate::WasmModPlugin;
+use crate::package::{ModManifest, ModPackage, ModPackages, PACKAGE_EXTENSION, is_package_path};
+use bevy::asset::{AssetLoader, LoadContext, LoadState, io::Reader};
+use bevy::prelude::*;
+use std::collections::HashSet;
+use std::fmt;
+use wasmtime::Module;
+
+/// A compiled wasm mod
+#[derive(Asset, TypePath, Clone)]
+pub struct WasmMod {
+    /// The compiled module
+    module: Module,
+    /// Manifest of the mod, if loaded from a package
+    manifest: Option<ModManifest>,
+}
+
+impl WasmMod {
+    /// Get the compiled module
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    /// Get the manifest of the mod, if loaded from a package
+    pub fn manifest(&self) -> Option<&ModManifest> {
+        self.manifest.as_ref()
+    }
+}
+
+/// Error while loading a [`WasmMod`]
+#[derive(Debug)]
+pub enum WasmModLoaderError {
+    /// Failed to read the wasm file
+    Io(std::io::Error),
+    /// Failed to read the mod package
+    Package(anyhow::Error),
+    /// Failed to compile the wasm module
+    Compile(anyhow::Error),
+}
+
+impl fmt::Display for WasmModLoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WasmModLoaderError::Io(e) => write!(f, "Failed to read wasm mod: {}", e),
+            WasmModLoaderError::Package(e) => write!(f, "Failed to read mod package: {}", e),
+            WasmModLoaderError::Compile(e) => write!(f, "Failed to compile wasm mod: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for WasmModLoaderError {}
+
+/// Asset loader compiling `.wasm` files and `.wmod` packages with the shared engine
+pub struct WasmModLoader {
+    /// The shared engine
+    engine: ModEngine,
+    /// Where the assets of packages are mounted
+    packages: ModPackages,
+}
+
+impl WasmModLoader {
+    /// Create a loader compiling with the engine and mounting package assets
+    pub fn new(engine: ModEngine, packages: ModPackages) -> Self {
+        Self { engine, packages }
+    }
+}
+
+impl AssetLoader for WasmModLoader {
+    type Asset = WasmMod;
+    type Settings = ();
+    type Error = WasmModLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<WasmMod, WasmModLoaderError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(WasmModLoaderError::Io)?;
+
+        if !is_package_path(load_context.path()) {
+            let module = self
+                .engine
+                .compile_module(&bytes)
+                .map_err(WasmModLoaderError::Compile)?;
+            return Ok(WasmMod {
+                module,
+                manifest: None,
+            });
+        }
+
+        let package = ModPackage::from_bytes(&bytes).map_err(WasmModLoaderError::Package)?;
+        let module = self
+            .engine
+            .compile_module(&package.module)
+            .map_err(WasmModLoaderError::Compile)?;
+        self.packages.mount(&package);
+
+        Ok(WasmMod {
+            module,
+            manifest: Some(package.manifest),
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["wasm", PACKAGE_EXTENSION]
+    }
+}
+
+/// Resource with the mods loaded as assets
+///
+/// Add a handle loaded from the `AssetServer` to run the mod once it is loaded.
+/// The mod is reloaded whenever its asset is modified.
+#[derive(Resource, Default)]
+pub struct ModAssets {
+    /// Handles of all mod assets
+    handles: Vec<Handle<WasmMod>>,
+    /// Mods waiting for their asset to be loaded
+    pending: HashSet<AssetId<WasmMod>>,
+}
+
+impl ModAssets {
+    /// Add a mod asset to run
+    pub fn add(&mut self, handle: Handle<WasmMod>) {
+        if self.contains(handle.id()) {
+            return;
+        }
+        self.pending.insert(handle.id());
+        self.handles.push(handle);
+    }
+
+    /// Whether the mod asset is tracked
+    pub fn contains(&self, id: AssetId<WasmMod>) -> bool {
+        self.handles.iter().any(|handle| handle.id() == id)
+    }
+
+    /// Iterate over the handles of all mod assets
+    pub fn iter(&self) -> impl Iterator<Item = &Handle<WasmMod>> {
+        self.handles.iter()
+    }
+}
+
+/// Load the mod assets set on the plugin
+pub(crate) fn load_mod_assets(
+    r_mod: Res<WasmModPlugin>,
+    asset_server: Res<AssetServer>,
+    mut r_mod_assets: ResMut<ModAssets>,
+) {
+    if r_mod.mod_assets.is_empty() {
+        return;
+    }
+    info!("loading mod assets: {:?}", r_mod.mod_assets);
+
+    for path in &r_mod.mod_assets {
+        r_mod_assets.add(asset_server.load(path.clone()));
+    }
+}
+
+/// Start instantiating the mod assets once they are loaded or modified
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn instantiate_mod_assets(
+    r_mod: Res<WasmModPlugin>,
+    r_engine: Res<ModEngine>,
+    asset_server: Res<AssetServer>,
+    wasm_mods: Res<Assets<WasmMod>>,
+    mut ev_assets: EventReader<AssetEvent<WasmMod>>,
+    mut r_mod_assets: ResMut<ModAssets>,
+    mut r_tasks: ResMut<ModLoadingTasks>,
+    mut r_state: ResMut<ModLoadingState>,
+    mut ev_loading: EventWriter<ModLoadingEvent>,
+) {
+    // Reload the modified mods
+    for event in ev_assets.read() {
+        if let AssetEvent::Modified { id } = event
+            && r_mod_assets.contains(*id)
+        {
+            r_mod_assets.pending.insert(*id);
+        }
+    }
+
+    if r_mod_assets.pending.is_empty() {
+        return;
+    }
+
+    let mut changes = Vec::new();
+    let pending: Vec<_> = r_mod_assets.pending.iter().copied().collect();
+    for id in pending {
+        let path = asset_server
+            .get_path(id)
+            .map(|path| path.to_string())
+            .unwrap_or_else(|| id.to_string());
+
+        if let Some(wasm_mod) = wasm_mods.get(id) {
+            r_tasks.instantiate(
+                path.clone(),
+                wasm_mod.module.clone(),
+                wasm_mod.manifest.clone(),
+                &r_mod,
+                &r_engine,
+            );
+            r_mod_assets.pending.remove(&id);
+            changes.push((path, ModLoadStatus::Pending));
+        } else if let Some(LoadState::Failed(e)) = asset_server.get_load_state(id) {
+            error!("Failed to load mod asset '{}': {}", path, e);
+            r_mod_assets.pending.remove(&id);
+            changes.push((path, ModLoadStatus::Failed(e.to_string())));
+        } else if r_state.get(&path).is_none() {
+            changes.push((path, ModLoadStatus::Pending));
+        }
+    }
+
+    // Report the changes
+    let changes: Vec<_> = changes
+        .into_iter()
+        .filter(|(path, status)| r_state.set_status(path, status.clone()))
+        .collect();
+    let (finished, total) = r_state.progress();
+    for (path, status) in changes {
+        ev_loading.write(ModLoadingEvent {
+            path,
+            status,
+            finished,
+            total,
+        });
+    }
+}
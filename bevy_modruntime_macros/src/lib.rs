@@ -3,62 +3,77 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 use quote::quote;
+use bevy_modmacros::{Instance, RegistrationArgs, reflect_ignored_fields};
 use syn::{DeriveInput, parse_macro_input};
 
 /// This macro is used to mark a component that can be accessed by mods.
 /// It will automatically implement serde serialization/deserialization and register the component.
+///
+/// Generic components are registered once per `instance(Type<..> = "id")` argument.
 #[proc_macro_attribute]
 pub fn mod_component(args: TokenStream, input: TokenStream) -> TokenStream {
     let derive_input = parse_macro_input!(input as DeriveInput);
-    let id = parse_id_from_args(args);
-
-    let struct_name = &derive_input.ident;
-    let component_id = if id.is_empty() {
-        struct_name.to_string()
-    } else {
-        id
+    let mut args_parsed = RegistrationArgs::with_namespaced();
+    let parser = syn::meta::parser(|meta| args_parsed.parse(meta));
+    parse_macro_input!(args with parser);
+    let instances = match args_parsed.instances(&derive_input) {
+        Ok(instances) => instances,
+        Err(e) => {
+            let error = e.to_compile_error();
+            return quote! { #derive_input #error }.into();
+        }
     };
 
-    // Generate a unique static variable name using a hash of the struct name
-    let static_name = format!(
-        "__MOD_COMPONENT_REGISTRATION_{}",
-        struct_name.to_string().to_uppercase()
-    );
-    let static_ident = syn::Ident::new(&static_name, struct_name.span());
+    let registrations = instances.iter().map(|Instance { ty, id, schema_hash }| {
+        let id = args_parsed.id_tokens(id);
+        // Each registration is in its own scope, so their names never collide
+        quote! {
+            const _: () = {
+                #[linkme::distributed_slice(bevy_modruntime::COMPONENT_REGISTRY)]
+                static REGISTRATION: bevy_modruntime::component::ComponentRegistration =
+                    bevy_modruntime::component::ComponentRegistration {
+                        id: #id,
+                        schema_hash: #schema_hash,
+                        serialize_fn: |component: bevy::ptr::Ptr<'_>, format: bevy_modruntime::Format| -> Vec<u8> {
+                            unsafe {
+                                let c = component.deref::<#ty>();
+                                format.encode(c).unwrap_or_else(|e| {
+                                    bevy::log::error!("Failed to serialize component {}: {}", #id, e);
+                                    Vec::new()
+                                })
+                            }
+                        },
+                        deserialize_fn: |data: &[u8], format: bevy_modruntime::Format| -> Result<Box<dyn std::any::Any>, bevy_modruntime::FormatError> {
+                            let value = format.decode::<#ty>(data)?;
+                            Ok(Box::new(value))
+                        },
+                        get_type_id: || -> std::any::TypeId {std::any::TypeId::of::<#ty>()},
+                        type_name: std::any::type_name::<#ty>,
+                        reg_fn: |registry: &mut bevy::reflect::TypeRegistry| {
+                            registry.register::<#ty>()
+                        },
+                        insert_fn: |entity: &mut bevy::ecs::world::EntityWorldMut, component: Box<dyn std::any::Any>| -> Result<(), bevy_modruntime::HostError> {
+                            let c = component
+                                .downcast::<#ty>()
+                                .map_err(|_| bevy_modruntime::HostError::InvalidData)?;
+                            entity.insert(*c);
+                            Ok(())
+                        }
+                    };
+            };
+        }
+    });
 
+    let ignored_fields = sdk_ignored_fields(&derive_input);
     let expanded = quote! {
-        // Original struct with serde and bincode derives
+        // Original type with serde derives
         #[derive(serde::Serialize, serde::Deserialize)]
         #[derive(bevy::prelude::Reflect)]
+        #ignored_fields
         #derive_input
 
-        // Component registration
-        #[linkme::distributed_slice(bevy_modruntime::COMPONENT_REGISTRY)]
-        #[warn(non_upper_case_globals)]
-        static #static_ident: bevy_modruntime::component::ComponentRegistration =
-            bevy_modruntime::component::ComponentRegistration {
-                id: #component_id,
-                serialize_fn: |component: bevy::ptr::Ptr<'_>| -> Vec<u8> {
-                    unsafe {
-                        let c = component.deref::<#struct_name>();
-                        bincode::serde::encode_to_vec(c, bincode::config::standard()).unwrap_or_else(|_| Vec::new())
-                    }
-                },
-                deserialize_fn: |data: &[u8]| -> Box<dyn std::any::Any> {
-                    Box::new(bincode::serde::decode_from_slice::
-                        <#struct_name, bincode::config::Configuration>(data, bincode::config::standard())
-                        .expect("Failed to deserialize component data").0)
-                },
-                get_type_id: || -> std::any::TypeId {std::any::TypeId::of::<#struct_name>()},
-                reg_fn: |mut registry: &mut bevy::reflect::TypeRegistry| {
-                    registry.register::<#struct_name>()
-                },
-                insert_fn: |entity: &mut bevy::ecs::world::EntityWorldMut, component: Box<dyn std::any::Any>| {
-                    if let Ok(c) = component.downcast::<#struct_name>() {
-                        entity.insert(*c);
-                    }
-                }
-            };
+        // Component registrations
+        #(#registrations)*
     };
 
     TokenStream::from(expanded)
@@ -66,70 +81,84 @@ pub fn mod_component(args: TokenStream, input: TokenStream) -> TokenStream {
 
 /// This macro is used to mark a resource that can be accessed by mods.
 /// It will automatically implement serde serialization/deserialization and register the resource.
+///
+/// Generic resources are registered once per `instance(Type<..> = "id")` argument.
 #[proc_macro_attribute]
 pub fn mod_resource(args: TokenStream, input: TokenStream) -> TokenStream {
     let derive_input = parse_macro_input!(input as DeriveInput);
-    let id = parse_id_from_args(args);
-
-    let struct_name = &derive_input.ident;
-    let resource_id = if id.is_empty() {
-        struct_name.to_string()
-    } else {
-        id
+    let mut args_parsed = RegistrationArgs::with_namespaced();
+    let parser = syn::meta::parser(|meta| args_parsed.parse(meta));
+    parse_macro_input!(args with parser);
+    let instances = match args_parsed.instances(&derive_input) {
+        Ok(instances) => instances,
+        Err(e) => {
+            let error = e.to_compile_error();
+            return quote! { #derive_input #error }.into();
+        }
     };
 
-    // Generate a unique static variable name using a hash of the struct name
-    let static_name = format!(
-        "__MOD_RESOURCE_REGISTRATION_{}",
-        struct_name.to_string().to_uppercase()
-    );
-    let static_ident = syn::Ident::new(&static_name, struct_name.span());
+    let registrations = instances.iter().map(|Instance { ty, id, schema_hash }| {
+        let id = args_parsed.id_tokens(id);
+        // Each registration is in its own scope, so their names never collide
+        quote! {
+            const _: () = {
+                #[linkme::distributed_slice(bevy_modruntime::RESOURCE_REGISTRY)]
+                static REGISTRATION: bevy_modruntime::resource::ResourceRegistration =
+                    bevy_modruntime::resource::ResourceRegistration {
+                        id: #id,
+                        schema_hash: #schema_hash,
+                        serialize_fn: |resource: bevy::ptr::Ptr<'_>, format: bevy_modruntime::Format| -> Vec<u8> {
+                            unsafe {
+                                let r = resource.deref::<#ty>();
+                                format.encode(r).unwrap_or_else(|e| {
+                                    bevy::log::error!("Failed to serialize resource {}: {}", #id, e);
+                                    Vec::new()
+                                })
+                            }
+                        },
+                        deserialize_fn: |data: &[u8], format: bevy_modruntime::Format| -> Result<Box<dyn std::any::Any>, bevy_modruntime::FormatError> {
+                            let value = format.decode::<#ty>(data)?;
+                            Ok(Box::new(value))
+                        },
+                        get_type_id: || -> std::any::TypeId {std::any::TypeId::of::<#ty>()},
+                        type_name: std::any::type_name::<#ty>,
+                        reg_fn: |registry: &mut bevy::reflect::TypeRegistry| {
+                            registry.register::<#ty>()
+                        },
+                        insert_fn: |world: &mut bevy::ecs::world::World, resource: Box<dyn std::any::Any>| -> Result<(), bevy_modruntime::HostError> {
+                            let r = resource
+                                .downcast::<#ty>()
+                                .map_err(|_| bevy_modruntime::HostError::InvalidData)?;
+                            world.insert_resource(*r);
+                            Ok(())
+                        }
+                    };
+            };
+        }
+    });
 
+    let ignored_fields = sdk_ignored_fields(&derive_input);
     let expanded = quote! {
-        // Original struct with serde and bincode derives
+        // Original type with serde derives
         #[derive(serde::Serialize, serde::Deserialize)]
         #[derive(bevy::prelude::Reflect)]
+        #ignored_fields
         #derive_input
 
-        // Resource registration
-        #[linkme::distributed_slice(RESOURCE_REGISTRY)]
-        #[warn(non_upper_case_globals)]
-        static #static_ident: bevy_modruntime::resource::ResourceRegistration =
-            bevy_modruntime::resource::ResourceRegistration {
-                id: #resource_id,
-                serialize_fn: |resource: bevy::ptr::Ptr<'_>| -> Vec<u8> {
-                    unsafe {
-                        let r = resource.deref::<#struct_name>();
-                        bincode::serde::encode_to_vec(r, bincode::config::standard()).unwrap_or_else(|_| Vec::new())
-                    }
-                },
-                deserialize_fn: |data: &[u8]| -> Box<dyn std::any::Any> {
-                    Box::new(bincode::serde::decode_from_slice::
-                        <#struct_name, bincode::config::Configuration>(data, bincode::config::standard())
-                        .expect("Failed to deserialize resource data").0)
-                },
-                get_type_id: || -> std::any::TypeId {std::any::TypeId::of::<#struct_name>()},
-                reg_fn: |mut registry: &mut bevy::reflect::TypeRegistry| {
-                    registry.register::<#struct_name>()
-                }
-            };
+        // Resource registrations
+        #(#registrations)*
     };
 
     TokenStream::from(expanded)
 }
 
-/// Parse the id from the macro arguments
-fn parse_id_from_args(args: TokenStream) -> String {
-    let args_str = args.to_string();
-
-    // Simple parsing for id = "value"
-    if let Some(id_start) = args_str.find("id = \"") {
-        let id_start = id_start + 6; // Length of id = "
-        if let Some(id_end) = args_str[id_start..].find("\"") {
-            return args_str[id_start..id_start + id_end].to_string();
-        }
+/// Record the fields ignored by reflection but serialized, so the SDK generator reports them
+fn sdk_ignored_fields(input: &DeriveInput) -> proc_macro2::TokenStream {
+    let fields = reflect_ignored_fields(input);
+    if fields.is_empty() {
+        return quote! {};
+    }
+    quote! {
+        #[reflect(@bevy_modruntime::sdk::IgnoredFields(vec![#(#fields.to_string()),*]))]
     }
-
-    // If no id is found, we'll use the struct name as default
-    String::new()
 }
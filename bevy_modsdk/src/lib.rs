@@ -4,4 +4,25 @@ pub use bevy_modsdk_macros::*;
 
 pub trait Component {
     fn component_id() -> &'static str;
+
+    /// Hash of the shape of the type, checked by the host, `0` to skip the check
+    fn schema_hash() -> u64 {
+        0
+    }
 }
+
+pub trait Resource {
+    fn resource_id() -> &'static str;
+
+    /// Hash of the shape of the type, checked by the host, `0` to skip the check
+    fn schema_hash() -> u64 {
+        0
+    }
+}
+
+/// Marker of the components and resources mods can write
+///
+/// Implemented by the `component` and `resource` macros. Types computed by the host, like
+/// `GlobalTransform`, do not implement it, so mods cannot query them as `&mut` or take them
+/// as `ResMut`.
+pub trait Mutable {}
@@ -4,65 +4,51 @@
 
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput, Meta, Lit, MetaNameValue};
-
-/// Parse the id from the macro arguments
-fn parse_id_from_args(args: proc_macro::TokenStream) -> String {
-    let args_str = args.to_string();
-    if args_str.is_empty() {
-        return String::new();
-    }
-
-    // Parse as meta attributes
-    let args_parsed = syn::parse::<Meta>(args).expect("Failed to parse args");
-    
-    match args_parsed {
-        Meta::NameValue(MetaNameValue { path, value, .. }) => {
-            if path.is_ident("id") {
-                match value {
-                    syn::Expr::Lit(lit) => {
-                        match lit.lit {
-                            Lit::Str(s) => s.value(),
-                            _ => panic!("Expected string literal for id"),
-                        }
-                    }
-                    _ => panic!("Expected literal for id"),
-                }
-            } else {
-                panic!("Expected 'id' attribute");
-            }
-        }
-        _ => panic!("Expected named value attribute"),
-    }
-}
+use bevy_modmacros::{Instance, RegistrationArgs};
+use syn::{DeriveInput, parse_macro_input};
 
 /// Component macro.
 ///
 /// This macro is used to mark a component that can be queried by mods.
 /// It will register the component with an ID and add serde support.
+/// Generic components get an ID per `instance(Type<..> = "id")` argument.
 #[proc_macro_attribute]
 pub fn component(args: TokenStream, input: TokenStream) -> TokenStream {
     let derive_input = parse_macro_input!(input as DeriveInput);
-    let id = parse_id_from_args(args);
-
-    let struct_name = &derive_input.ident;
-    let component_id = if id.is_empty() {
-        struct_name.to_string()
-    } else {
-        id
+    let mut args_parsed = RegistrationArgs::default();
+    let parser = syn::meta::parser(|meta| args_parsed.parse(meta));
+    parse_macro_input!(args with parser);
+    let instances = match args_parsed.instances(&derive_input) {
+        Ok(instances) => instances,
+        Err(e) => {
+            let error = e.to_compile_error();
+            return quote! { #derive_input #error }.into();
+        }
     };
 
+    let impls = instances.iter().map(|Instance { ty, id, schema_hash }| {
+        quote! {
+            impl bevy_modsdk::Component for #ty {
+                fn component_id() -> &'static str {
+                    #id
+                }
+
+                fn schema_hash() -> u64 {
+                    #schema_hash
+                }
+            }
+
+            impl bevy_modsdk::Mutable for #ty {}
+        }
+    });
+
     let expanded = quote! {
-        // Original struct with serde derives for serialization/deserialization
+        // Original type with serde derives for serialization/deserialization
         #[derive(serde::Serialize, serde::Deserialize)]
         #derive_input
 
-        // // Add component trait 
-        impl bevy_modsdk::Component for #struct_name {
-            fn component_id() -> &'static str {
-                #component_id
-            }
-        }
+        // Add component trait
+        #(#impls)*
     };
 
     TokenStream::from(expanded)
@@ -72,30 +58,52 @@ pub fn component(args: TokenStream, input: TokenStream) -> TokenStream {
 ///
 /// This macro is used to mark a resource that can be queried by mods.
 /// It will register the resource with an ID and add serde support.
+/// Generic resources get an ID per `instance(Type<..> = "id")` argument.
 #[proc_macro_attribute]
 pub fn resource(args: TokenStream, input: TokenStream) -> TokenStream {
     let derive_input = parse_macro_input!(input as DeriveInput);
-    let id = parse_id_from_args(args);
-
-    let struct_name = &derive_input.ident;
-    let resource_id = if id.is_empty() {
-        struct_name.to_string()
-    } else {
-        id
+    let mut args_parsed = RegistrationArgs::default();
+    let parser = syn::meta::parser(|meta| args_parsed.parse(meta));
+    parse_macro_input!(args with parser);
+    let instances = match args_parsed.instances(&derive_input) {
+        Ok(instances) => instances,
+        Err(e) => {
+            let error = e.to_compile_error();
+            return quote! { #derive_input #error }.into();
+        }
     };
 
+    let impls = instances.iter().map(|Instance { ty, id, schema_hash }| {
+        quote! {
+            // Resource ID registration
+            impl #ty {
+                /// Get the resource ID
+                pub const fn resource_id() -> &'static str {
+                    #id
+                }
+            }
+
+            // Add resource trait
+            impl bevy_modsdk::Resource for #ty {
+                fn resource_id() -> &'static str {
+                    #id
+                }
+
+                fn schema_hash() -> u64 {
+                    #schema_hash
+                }
+            }
+
+            impl bevy_modsdk::Mutable for #ty {}
+        }
+    });
+
     let expanded = quote! {
-        // Original struct with serde derives for serialization/deserialization
+        // Original type with serde derives for serialization/deserialization
         #[derive(serde::Serialize, serde::Deserialize)]
         #derive_input
 
-        // Resource ID registration
-        impl #struct_name {
-            /// Get the resource ID
-            pub const fn resource_id() -> &'static str {
-                #resource_id
-            }
-        }
+        #(#impls)*
     };
 
     TokenStream::from(expanded)
@@ -0,0 +1,192 @@
+//! Serialization formats of the data exchanged between the host and mods.
+//!
+//! Bincode is always available, postcard and MessagePack with the `postcard` and `msgpack`
+//! features. The host picks the format of each mod when loading it, among the formats
+//! both sides support.
+
+use serde::de::{DeserializeOwned, DeserializeSeed};
+use serde::Serialize;
+
+/// Serialization format of the data exchanged between the host and mods.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// Bincode with its standard config.
+    #[default]
+    Bincode,
+    /// Postcard, the most compact.
+    Postcard,
+    /// MessagePack, with structs as arrays.
+    MessagePack,
+}
+
+impl Format {
+    /// All formats, in the order of their indices.
+    pub const ALL: [Format; 3] = [Format::Bincode, Format::Postcard, Format::MessagePack];
+
+    /// Get a format from its index, as passed between the host and mods.
+    pub fn from_index(index: u32) -> Option<Self> {
+        Self::ALL.get(index as usize).copied()
+    }
+
+    /// Get the index of the format.
+    pub fn index(self) -> u32 {
+        self as u32
+    }
+
+    /// Whether the format is compiled in.
+    pub fn is_supported(self) -> bool {
+        match self {
+            Format::Bincode => true,
+            Format::Postcard => cfg!(feature = "postcard"),
+            Format::MessagePack => cfg!(feature = "msgpack"),
+        }
+    }
+
+    /// Get the supported formats as a bit mask of their indices.
+    pub fn supported_mask() -> u32 {
+        Self::ALL
+            .iter()
+            .filter(|format| format.is_supported())
+            .fold(0, |mask, format| mask | 1 << format.index())
+    }
+
+    /// Pick the format to use with the other side, from the formats it supports.
+    ///
+    /// The preferred format is used if both sides support it, bincode otherwise.
+    pub fn negotiate(preferred: Format, other_mask: u32) -> Format {
+        if preferred.is_supported() && other_mask & (1 << preferred.index()) != 0 {
+            preferred
+        } else {
+            Format::Bincode
+        }
+    }
+
+    /// Serialize a value.
+    pub fn encode<T: Serialize + ?Sized>(self, value: &T) -> Result<Vec<u8>, FormatError> {
+        match self {
+            Format::Bincode => bincode::serde::encode_to_vec(value, bincode::config::standard())
+                .map_err(|e| FormatError::Encode(e.to_string())),
+            #[cfg(feature = "postcard")]
+            Format::Postcard => {
+                postcard::to_allocvec(value).map_err(|e| FormatError::Encode(e.to_string()))
+            }
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => {
+                rmp_serde::to_vec(value).map_err(|e| FormatError::Encode(e.to_string()))
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(FormatError::Unsupported(self)),
+        }
+    }
+
+    /// Deserialize a value.
+    pub fn decode<T: DeserializeOwned>(self, data: &[u8]) -> Result<T, FormatError> {
+        match self {
+            Format::Bincode => bincode::serde::decode_from_slice(data, bincode::config::standard())
+                .map(|(value, _)| value)
+                .map_err(|e| FormatError::Decode(e.to_string())),
+            #[cfg(feature = "postcard")]
+            Format::Postcard => {
+                postcard::from_bytes(data).map_err(|e| FormatError::Decode(e.to_string()))
+            }
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => {
+                rmp_serde::from_slice(data).map_err(|e| FormatError::Decode(e.to_string()))
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(FormatError::Unsupported(self)),
+        }
+    }
+
+    /// Deserialize a value with a seed, like the reflect deserializer of Bevy.
+    pub fn decode_seed<'de, S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+        data: &'de [u8],
+    ) -> Result<S::Value, FormatError> {
+        match self {
+            Format::Bincode => {
+                bincode::serde::seed_decode_from_slice(seed, data, bincode::config::standard())
+                    .map(|(value, _)| value)
+                    .map_err(|e| FormatError::Decode(e.to_string()))
+            }
+            #[cfg(feature = "postcard")]
+            Format::Postcard => {
+                let mut deserializer = postcard::Deserializer::from_bytes(data);
+                seed.deserialize(&mut deserializer)
+                    .map_err(|e| FormatError::Decode(e.to_string()))
+            }
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => {
+                let mut deserializer = rmp_serde::Deserializer::from_read_ref(data);
+                seed.deserialize(&mut deserializer)
+                    .map_err(|e| FormatError::Decode(e.to_string()))
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(FormatError::Unsupported(self)),
+        }
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Bincode => write!(f, "bincode"),
+            Format::Postcard => write!(f, "postcard"),
+            Format::MessagePack => write!(f, "MessagePack"),
+        }
+    }
+}
+
+/// Error while serializing or deserializing data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatError {
+    /// The format is not compiled in.
+    Unsupported(Format),
+    /// The value could not be serialized.
+    Encode(String),
+    /// The data could not be deserialized.
+    Decode(String),
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormatError::Unsupported(format) => write!(f, "format {} is not supported", format),
+            FormatError::Encode(e) => write!(f, "failed to serialize: {}", e),
+            FormatError::Decode(e) => write!(f, "failed to deserialize: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supported_mask_has_bincode() {
+        let mask = Format::supported_mask();
+        assert_ne!(mask & 1 << Format::Bincode.index(), 0);
+        for format in Format::ALL {
+            assert_eq!(mask & 1 << format.index() != 0, format.is_supported());
+        }
+    }
+
+    #[test]
+    fn negotiate_falls_back_to_bincode() {
+        let bincode_only = 1 << Format::Bincode.index();
+        for format in Format::ALL {
+            assert_eq!(Format::negotiate(format, bincode_only), Format::Bincode);
+        }
+        assert_eq!(Format::negotiate(Format::Postcard, 0), Format::Bincode);
+    }
+
+    #[test]
+    fn negotiate_keeps_supported_preference() {
+        for format in Format::ALL.into_iter().filter(|format| format.is_supported()) {
+            assert_eq!(Format::negotiate(format, u32::MAX), format);
+        }
+    }
+}
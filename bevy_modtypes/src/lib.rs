@@ -1,5 +1,9 @@
 //! Shared type
 
+pub mod format;
+
+pub use format::{Format, FormatError};
+
 /// System info
 #[repr(C)]
 #[derive(Debug)]
@@ -23,3 +27,304 @@ pub struct HostModResult {
     /// Length of the serialized data
     pub data_len: u32,
 }
+
+/// Data requested by one parameter of a mod system.
+///
+/// A list of these is sent to the host in a single call before the system runs.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum SystemParamRequest {
+    /// Query entities having all the listed components.
+    Query {
+        /// Component ids, in the order they appear in the query.
+        components: Vec<String>,
+    },
+    /// Read a resource.
+    Resource {
+        /// The resource id.
+        id: String,
+    },
+}
+
+/// Data fetched by the host for one parameter of a mod system.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum SystemParamData {
+    /// Matched entities as `(entity bits, serialized components)`.
+    Query(Vec<(u64, Vec<Vec<u8>>)>),
+    /// The serialized resource, or `None` if it does not exist.
+    Resource(Option<Vec<u8>>),
+}
+
+/// Serialized components of one entity as `[(component id, data)]`.
+pub type ComponentsData = Vec<(String, Vec<u8>)>;
+
+/// Mutations made by a mod system, applied by the host after the system returns.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct SystemWrites {
+    /// Components to write back as `(entity bits, [(component id, data)])`.
+    pub components: Vec<(u64, ComponentsData)>,
+    /// Resources to write back as `(resource id, data)`.
+    pub resources: Vec<(String, Vec<u8>)>,
+}
+
+/// Components and resources accessed by a mod system, declared through its parameters.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SystemAccess {
+    /// Ids of the components read by the system.
+    pub component_reads: Vec<String>,
+    /// Ids of the components written by the system.
+    pub component_writes: Vec<String>,
+    /// Ids of the resources read by the system.
+    pub resource_reads: Vec<String>,
+    /// Ids of the resources written by the system.
+    pub resource_writes: Vec<String>,
+}
+
+impl SystemAccess {
+    /// Add all the accesses of another system.
+    pub fn extend(&mut self, other: &SystemAccess) {
+        self.component_reads.extend(other.component_reads.iter().cloned());
+        self.component_writes.extend(other.component_writes.iter().cloned());
+        self.resource_reads.extend(other.resource_reads.iter().cloned());
+        self.resource_writes.extend(other.resource_writes.iter().cloned());
+    }
+
+    /// Whether the two accesses can run at the same time.
+    ///
+    /// They cannot if one writes a component or resource the other reads or writes.
+    pub fn is_compatible(&self, other: &SystemAccess) -> bool {
+        fn disjoint(writes: &[String], reads: &[String], other_writes: &[String]) -> bool {
+            writes
+                .iter()
+                .all(|id| !reads.contains(id) && !other_writes.contains(id))
+        }
+
+        disjoint(&self.component_writes, &other.component_reads, &other.component_writes)
+            && disjoint(&other.component_writes, &self.component_reads, &self.component_writes)
+            && disjoint(&self.resource_writes, &other.resource_reads, &other.resource_writes)
+            && disjoint(&other.resource_writes, &self.resource_reads, &self.resource_writes)
+    }
+}
+
+/// A component defined by a mod, unknown to the host.
+///
+/// The host stores its data as the bytes serialized by the mods.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ModComponentDef {
+    /// The component id.
+    pub id: String,
+    /// Hash of the shape of the type, checked against the other mods, `0` to skip the check.
+    pub schema_hash: u64,
+}
+
+/// A resource defined by a mod, unknown to the host.
+///
+/// The host stores its data as the bytes serialized by the mods.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ModResourceDef {
+    /// The resource id.
+    pub id: String,
+    /// Hash of the shape of the type, checked against the other mods, `0` to skip the check.
+    pub schema_hash: u64,
+    /// The serialized initial value, used when the resource does not exist yet.
+    pub initial: Vec<u8>,
+}
+
+/// Level of a log record sent by a mod, from the most to the least verbose.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    /// Very verbose tracing.
+    Trace,
+    /// Debugging information.
+    Debug,
+    /// General information.
+    Info,
+    /// Something unexpected, but recoverable.
+    Warn,
+    /// Something failed.
+    Error,
+}
+
+impl LogLevel {
+    /// Get a level from its index, as passed between the host and mods.
+    pub fn from_index(index: u32) -> Option<Self> {
+        match index {
+            0 => Some(LogLevel::Trace),
+            1 => Some(LogLevel::Debug),
+            2 => Some(LogLevel::Info),
+            3 => Some(LogLevel::Warn),
+            4 => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+
+    /// Get the index of the level.
+    pub fn index(self) -> u32 {
+        self as u32
+    }
+}
+
+/// A log record sent by a mod.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct LogRecord {
+    /// The level of the record.
+    pub level: LogLevel,
+    /// The formatted message.
+    pub message: String,
+    /// Structured fields as `(key, value)`.
+    pub fields: Vec<(String, String)>,
+}
+
+/// Kind of a type whose schema is checked by the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SchemaKind {
+    /// A component.
+    Component,
+    /// A resource.
+    Resource,
+}
+
+impl SchemaKind {
+    /// Get a kind from its index, as passed between the host and mods.
+    pub fn from_index(index: u32) -> Option<Self> {
+        match index {
+            0 => Some(SchemaKind::Component),
+            1 => Some(SchemaKind::Resource),
+            _ => None,
+        }
+    }
+
+    /// Get the index of the kind.
+    pub fn index(self) -> u32 {
+        self as u32
+    }
+}
+
+impl std::fmt::Display for SchemaKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaKind::Component => write!(f, "component"),
+            SchemaKind::Resource => write!(f, "resource"),
+        }
+    }
+}
+
+/// Normalize the tokens of a field type in a schema.
+///
+/// Whitespace and module paths are removed, so `bevy_math :: Vec2` and `Vec2` match.
+pub fn normalize_schema_type(tokens: &str) -> String {
+    let mut normalized = String::with_capacity(tokens.len());
+    let mut chars = tokens.chars().filter(|c| !c.is_whitespace()).peekable();
+    while let Some(c) = chars.next() {
+        if c == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            // Drop the path segment before `::`
+            while normalized
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_')
+            {
+                normalized.pop();
+            }
+            continue;
+        }
+        normalized.push(c);
+    }
+    normalized
+}
+
+/// Hash the schema of a type, describing its shape.
+///
+/// Computed by the component and resource macros of the host and of the SDK, and compared
+/// by the host when a mod first uses the type. `0` is reserved for unchecked types.
+pub const fn schema_hash(schema: &str) -> u64 {
+    // FNV-1a, stable across builds and targets
+    let bytes = schema.as_bytes();
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+        i += 1;
+    }
+    if hash == 0 { 1 } else { hash }
+}
+
+/// Error returned by the host to a mod, as a non-zero code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HostError {
+    /// The data sent by the mod could not be decoded.
+    InvalidData = 1,
+    /// A component or resource id is not registered.
+    UnknownId = 2,
+    /// The schema of a component or resource differs from the host.
+    SchemaMismatch = 3,
+    /// An entity does not exist.
+    NoEntity = 4,
+    /// The host could not reach the world or the memory of the mod.
+    Unavailable = 5,
+    /// A component or resource is computed by the host and cannot be written by mods.
+    ReadOnly = 6,
+}
+
+impl HostError {
+    /// Get the result of a host call from its code, `0` being a success.
+    pub fn from_code(code: u32) -> Result<(), Self> {
+        match code {
+            0 => Ok(()),
+            1 => Err(HostError::InvalidData),
+            2 => Err(HostError::UnknownId),
+            3 => Err(HostError::SchemaMismatch),
+            4 => Err(HostError::NoEntity),
+            6 => Err(HostError::ReadOnly),
+            _ => Err(HostError::Unavailable),
+        }
+    }
+
+    /// Get the code of the result of a host call, `0` being a success.
+    pub fn code(result: Result<(), Self>) -> u32 {
+        match result {
+            Ok(()) => 0,
+            Err(e) => e as u32,
+        }
+    }
+}
+
+impl std::fmt::Display for HostError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HostError::InvalidData => write!(f, "invalid data"),
+            HostError::UnknownId => write!(f, "unknown component or resource id"),
+            HostError::SchemaMismatch => write!(f, "schema differs from the host"),
+            HostError::NoEntity => write!(f, "entity does not exist"),
+            HostError::Unavailable => write!(f, "host unavailable"),
+            HostError::ReadOnly => write!(f, "component or resource is read-only"),
+        }
+    }
+}
+
+impl std::error::Error for HostError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_schema_type_drops_paths_and_spaces() {
+        assert_eq!(normalize_schema_type("bevy :: math :: Vec2"), "Vec2");
+        assert_eq!(
+            normalize_schema_type("Vec < std :: string :: String >"),
+            "Vec<String>"
+        );
+        assert_eq!(normalize_schema_type("[f32 ; 3]"), "[f32;3]");
+    }
+
+    #[test]
+    fn schema_hash_is_never_zero() {
+        assert_ne!(schema_hash(""), 0);
+        assert_eq!(schema_hash("{x:f32}"), schema_hash("{x:f32}"));
+        assert_ne!(schema_hash("{x:f32}"), schema_hash("{x:f64}"));
+    }
+}
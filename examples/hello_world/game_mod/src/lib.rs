@@ -17,7 +17,11 @@ pub fn example_startup_system() {
     let asset_id = asset_def!(type=text, src="example_asset.txt");
     log_info!("Defined asset with ID: {}", asset_id);
     
-    spawn!(Square(Vec2 { x: 100.0, y: 120.6 }), Rect(IVec2 { x: 60, y: 66 }));
+    spawn!(
+        Square(Vec2 { x: 100.0, y: 120.6 }),
+        Rect(IVec2 { x: 60, y: 66 }),
+        Sprite::from_color([0.2, 0.6, 1.0, 1.0], [60.0, 66.0])
+    );
     spawn!(Square(Vec2 { x: 100.0, y: 120.3 }), Rect(IVec2 { x: 60, y: 88 }));
 }
 
@@ -46,8 +50,26 @@ pub fn spawn_entities_system() {
     if entities.len() < 3 {
         log_info!("Spawning a new entity, current count: {}", entities.len());
         spawn!(Square(Vec2 { x: 100.0, y: 120.0 }), Rect(IVec2 { x: 60, y: 64 }));
+    } else {
+        // Enough entities, this system is no longer needed
+        system::set_system_enabled("spawn_entities_system", false);
+    }
+}
+
+#[system]
+pub fn move_squares_system(mut squares: Query<(Entity, &mut Square, &Rect)>, player: Res<Player>) {
+    // Move every square a little towards the player
+    for (entity, mut square, rect) in squares.iter_mut() {
+        let step = (player.0 - square.0) * 0.01;
+        square.0 += step;
+        log_info!("From Mod: Moved {:?} with rect {:?} to {:?}", entity, rect.0, square.0);
     }
 }
 
 // Define the mod and list all its systems
-system_def!(example_startup_system, example_update_system, spawn_entities_system);
+system_def!(
+    example_startup_system,
+    example_update_system,
+    spawn_entities_system,
+    move_squares_system
+);
@@ -1,14 +1,14 @@
-//! Example of game sdk.
+//! Components and resources of the host
 //!
-//! Used for complex structure interaction with the Host. Ensure that the definition is the same as the Host
+//! Generated by `bevy_modruntime::SdkGenerator`, do not edit.
 
 use bevy_modsdk::{component, resource};
 
-#[component(id = "square")]
-pub struct Square(pub bevy_math::Vec2);
-
 #[component(id = "rect")]
 pub struct Rect(pub bevy_math::IVec2);
 
+#[component(id = "square")]
+pub struct Square(pub bevy_math::Vec2);
+
 #[resource(id = "player")]
 pub struct Player(pub bevy_math::Vec2);
@@ -4,9 +4,10 @@
 //! The host is responsible for loading mods, setting up the WebAssembly runtime,
 //! and coordinating communication between mods and the main game.
 
-use bevy::{log::LogPlugin, prelude::*};
+use bevy::prelude::*;
 use bevy_modruntime::{
-    AssetInfo, COMPONENT_REGISTRY, RESOURCE_REGISTRY, WasmModPlugin, mod_component, mod_resource,
+    AssetInfo, COMPONENT_REGISTRY, RESOURCE_REGISTRY, SdkGenerator, WasmModPlugin, mod_component,
+    mod_resource, new_builtin_asset,
 };
 
 #[mod_component(id = "square")]
@@ -48,24 +49,32 @@ fn insert_example_resource(mut commands: Commands) {
 }
 
 /// Handle new asset from mod
-fn handle_new_asset(_world: &mut World, asset_info: AssetInfo) -> String {
+fn handle_new_asset(world: &mut World, asset_info: AssetInfo) -> String {
     info!("New asset from mod:");
     info!("  Mod name: {}", asset_info.mod_name);
     info!("  Asset type: {}", asset_info.asset_type);
+    info!("  Asset path: {}", asset_info.asset_path);
     info!(
         "  Asset data: {}",
         String::from_utf8_lossy(&asset_info.asset_data)
     );
 
-    // For now, just return an empty string as the asset ID
-    // In a real implementation, you would generate a unique ID for the asset
-    String::new()
+    // Let the built-in handling turn the data into a Bevy asset and return its ID
+    new_builtin_asset(world, asset_info)
 }
 
 fn main() {
+    // Regenerate the game sdk from the components and resources above
+    if std::env::args().any(|arg| arg == "--generate-sdk") {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../game_sdk");
+        SdkGenerator::new("game_sdk")
+            .write_lib(dir)
+            .expect("Failed to generate the game sdk");
+        return;
+    }
+
     App::new()
-        // .add_plugins(MinimalPlugins)
-        .add_plugins(LogPlugin::default())
+        // Added before DefaultPlugins, so mods can define assets in the `mod://` source
         .add_plugins(
             WasmModPlugin::default()
                 .add_mod_path(
@@ -74,6 +83,7 @@ fn main() {
                 )
                 .set_new_asset_fn(handle_new_asset),
         )
+        .add_plugins(DefaultPlugins)
         .add_systems(Startup, print_component_registry)
         .add_systems(Startup, print_resource_registry)
         .add_systems(Startup, spawn_example_component)